@@ -10,23 +10,59 @@ create table if not exists website (
 --- 浏览量
 create table if not exists page_view_counter (
     id serial primary key,
-    site_id int not null,
+    site_id int not null default 0,
     path varchar(255) not null,
     times int not null default 0,
-    reaction0 int not null default 0,
-    reaction1 int not null default 0,
-    reaction2 int not null default 0,
-    reaction3 int not null default 0,
-    reaction4 int not null default 0,
-    reaction5 int not null default 0,
-    reaction6 int not null default 0,
-    reaction7 int not null default 0,
-    reaction8 int not null default 0,
+    title varchar(255) default null,
+    -- 锁定后不能再发表评论
+    locked boolean not null default false,
+    -- 隐藏整个评论区
+    hidden boolean not null default false,
     created_at timestamp not null default current_timestamp,
     updated_at timestamp not null default current_timestamp
 );
+-- alter table page_view_counter add column if not exists title varchar(255) default null;
+-- alter table page_view_counter add column if not exists locked boolean not null default false;
+-- alter table page_view_counter add column if not exists hidden boolean not null default false;
 --- site_id, path字段创建唯一索引，叶子节点包含id针对频繁根据path查询id，避免回表，同时redis也要做好缓存
 create unique index if not exists page_view_counter_uk_site_path on page_view_counter(site_id, path) include (id);
+--- 页面改名或合并后，旧路径作为别名指向新页面
+create table if not exists page_aliases (
+    path varchar(255) primary key,
+    page_id int not null,
+    created_at timestamp not null default current_timestamp
+);
+create index if not exists page_aliases_idx_page_id on page_aliases(page_id);
+--- 页面表态
+create table if not exists page_reactions (
+    page_id int not null,
+    reaction_key varchar(32) not null,
+    count int not null default 0,
+    primary key (page_id, reaction_key)
+);
+--- 页面表态的访客，用于去重和取消表态
+create table if not exists page_reaction_voters (
+    page_id int not null,
+    reaction_key varchar(32) not null,
+    voter varchar(64) not null,
+    created_at timestamp not null default current_timestamp,
+    primary key (page_id, reaction_key, voter)
+);
+--- 页面每天的浏览量统计，dimension为total/referrer/country/browser/os
+create table if not exists page_view_stats (
+    page_id int not null,
+    day date not null,
+    dimension varchar(16) not null,
+    value varchar(255) not null default '',
+    times int not null default 0,
+    primary key (page_id, day, dimension, value)
+);
+create index if not exists page_view_stats_idx_day on page_view_stats(day, dimension);
+--- 旧版本page_view_counter.reaction0..reaction8的迁移
+-- insert into page_reactions(page_id, reaction_key, count)
+-- select id, 'reaction' || n, (array[reaction0, reaction1, reaction2, reaction3, reaction4, reaction5, reaction6, reaction7, reaction8])[n + 1]
+-- from page_view_counter, generate_series(0, 8) as n
+-- on conflict do nothing;
 --- 评论状态
 create type comment_status as enum('waiting', 'approved', 'spam');
 --- 用户评论
@@ -40,18 +76,94 @@ create table if not exists comments (
     nick varchar(255) default null,
     pid int not null default 0,
     rid int not null default 0,
+    -- 回复的层级，根评论为0
+    depth int not null default 0,
     sticky boolean not null default 'false',
     status comment_status not null,
-    vote_up int not null default 0,
+    star int not null default 0,
     vote_down int not null default 0,
     ip varchar(255) not null,
     ua text not null,
+    moderation jsonb default null,
+    -- 回复时@的评论：{"pid": 1, "nick": "xxx"}
+    mention jsonb default null,
+    -- 渲染后的html缓存，render_version和当前渲染配置不一致时需要重新渲染
+    comment_html text default null,
+    render_version bigint not null default 0,
+    deleted_at timestamp default null,
+    edited_at timestamp default null,
     created_at timestamp not null default current_timestamp,
     updated_at timestamp not null default current_timestamp
 );
 create index if not exists comments_idx_pgid_rid_sticky_created on comments(page_id, rid, sticky desc, created_at desc) include (star, status, user_id);
 create index if not exists comments_idx_rid on comments(rid);
 create index if not exists comments_idx_pid on comments(pid);
+--- 全文检索，配置需要和[search].ts_config一致，使用zhparser时将'simple'改为'chinese'
+--- 公开搜索也使用该字段，不能包含mail，否则可以通过邮箱查出某人的评论
+alter table comments add column if not exists search_vector tsvector generated always as (
+    to_tsvector('simple', coalesce(nick, '') || ' ' || content)
+) stored;
+--- 旧版本search_vector包含mail，需要重建
+-- alter table comments drop column if exists search_vector;
+create index if not exists comments_idx_search_vector on comments using gin(search_vector);
+--- 使用pg_bigm时可以额外创建索引加速中文的like查询
+-- create index if not exists comments_idx_content_bigm on comments using gin(content gin_bigm_ops);
+--- 旧版本content中的`[@nick](#pid): `迁移到mention
+-- alter table comments add column if not exists mention jsonb default null;
+-- update comments
+-- set mention = jsonb_build_object('pid', substring(content from '^\[@.*?\]\(#(\d+)\): ')::int,
+--                                  'nick', substring(content from '^\[@(.*?)\]\(#\d+\): ')),
+--     content = regexp_replace(content, '^\[@.*?\]\(#\d+\): ', '')
+-- where content ~ '^\[@.*?\]\(#\d+\): ';
+--- 旧版本没有html缓存，添加字段后调用POST /api/comment/rebuild-html生成
+-- alter table comments add column if not exists comment_html text default null;
+-- alter table comments add column if not exists render_version bigint not null default 0;
+--- 旧版本comments.depth的迁移
+-- alter table comments add column if not exists depth int not null default 0;
+-- with recursive tree(id, depth) as (
+--     select id, 0 from comments where pid = 0
+--     union all
+--     select c.id, t.depth + 1 from comments c join tree t on c.pid = t.id
+-- )
+-- update comments set depth = tree.depth from tree where comments.id = tree.id;
+--- 旧版本没有审核结论
+-- alter table comments add column if not exists moderation jsonb default null;
+--- 旧版本没有软删除
+-- alter table comments add column if not exists deleted_at timestamp default null;
+--- 旧版本没有编辑时间
+-- alter table comments add column if not exists edited_at timestamp default null;
+--- 旧版本的点赞数字段为vote_up
+-- alter table comments rename column vote_up to star;
+--- 评论编辑历史
+create table if not exists comment_revisions (
+    id serial primary key,
+    comment_id int not null,
+    user_id int default null,
+    content text not null,
+    created_at timestamp not null default current_timestamp
+);
+create index if not exists comment_revisions_idx_comment_id on comment_revisions(comment_id);
+--- 评论点赞/点踩，voter为"user:{uid}"或者"anon:{hash(ip+ua)}"
+create table if not exists comment_votes (
+    id serial primary key,
+    comment_id int not null,
+    user_id int default null,
+    voter varchar(64) not null,
+    vote smallint not null,
+    created_at timestamp not null default current_timestamp,
+    updated_at timestamp not null default current_timestamp
+);
+create unique index if not exists comment_votes_uk_comment_voter on comment_votes(comment_id, voter);
+--- 评论表情回应
+create table if not exists comment_reactions (
+    id serial primary key,
+    comment_id int not null,
+    user_id int default null,
+    voter varchar(64) not null,
+    reaction varchar(32) not null,
+    created_at timestamp not null default current_timestamp
+);
+create unique index if not exists comment_reactions_uk_comment_reaction_voter on comment_reactions(comment_id, reaction, voter);
 --- 用户类型
 create type user_type as enum('admin', 'normal');
 create type user_gender as enum('unknown', 'male', 'female');
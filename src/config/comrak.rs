@@ -1,8 +1,8 @@
 use comrak::Options;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use spring::config::Configurable;
 
-#[derive(Clone, Deserialize, Configurable)]
+#[derive(Clone, Serialize, Deserialize, Configurable)]
 #[config_prefix = "comrak"]
 pub struct ComrakConfig {
     #[serde(default = "default_true")]
@@ -41,6 +41,15 @@ pub struct ComrakConfig {
     pub spoiler: bool,
     #[serde(default = "default_true")]
     pub greentext: bool,
+    /// 服务端使用syntect高亮代码块
+    #[serde(default)]
+    pub highlight: bool,
+    /// 高亮主题，如InspiredGitHub、base16-ocean.dark，会输出style属性，需要在[sanitizer]中允许；
+    /// 不配置时输出css class，由前端引入样式
+    pub highlight_theme: Option<String>,
+    /// 服务端把公式渲染成MathML
+    #[serde(default)]
+    pub math_render: bool,
 }
 
 fn default_true() -> bool {
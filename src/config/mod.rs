@@ -1,8 +1,13 @@
 pub mod akismet;
-pub mod comrak;
-pub mod mail;
 pub mod auth;
+pub mod comrak;
 pub mod ip2region;
+pub mod mail;
+pub mod pageview;
+pub mod path;
+pub mod reaction;
+pub mod sanitizer;
+pub mod search;
 
 use serde::Deserialize;
 use spring::config::Configurable;
@@ -27,6 +32,33 @@ pub struct RalineConfig {
     pub disable_region: bool,
     #[serde(default)]
     pub forbidden_words: Vec<String>,
+    /// 自动清理超过指定天数的垃圾评论，不配置则不清理
+    pub spam_retention_days: Option<u64>,
+    /// 普通用户发表评论后允许编辑的秒数，不配置则不限制
+    pub edit_window: Option<u64>,
+    /// 评论列表中每条根评论最多返回的回复数，更多的回复通过rid和游标分页查询
+    #[serde(default = "default_reply_limit")]
+    pub reply_limit: u64,
+    /// 回复的最大层级，超过时挂到允许的最深一层祖先评论下，不配置则不限制
+    pub max_depth: Option<i32>,
+    /// 每个ip每分钟最多预览的次数
+    #[serde(default = "default_preview_limit")]
+    pub preview_limit: u64,
+    /// 评论内容的最小字符数
+    #[serde(default = "default_comment_min_length")]
+    pub comment_min_length: usize,
+    /// 评论内容的最大字符数，预览同样受此限制
+    #[serde(default = "default_comment_max_length")]
+    pub comment_max_length: usize,
+    /// 匿名评论必填的字段，同Waline的requiredMeta
+    #[serde(default)]
+    pub required_meta: Vec<RequiredMeta>,
+    /// 必须登录才能评论，同Waline的login = "force"
+    #[serde(default)]
+    pub login_required: bool,
+    /// 评论者网址允许的协议
+    #[serde(default = "default_link_schemes")]
+    pub link_schemes: Vec<String>,
     pub recaptcha_v3_key: Option<String>,
     pub turnstile_key: Option<String>,
 }
@@ -34,3 +66,31 @@ pub struct RalineConfig {
 fn default_ip_qps() -> u64 {
     60
 }
+
+fn default_reply_limit() -> u64 {
+    20
+}
+
+fn default_preview_limit() -> u64 {
+    30
+}
+
+fn default_comment_min_length() -> usize {
+    1
+}
+
+fn default_comment_max_length() -> usize {
+    10000
+}
+
+fn default_link_schemes() -> Vec<String> {
+    vec!["http".to_string(), "https".to_string()]
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequiredMeta {
+    Nick,
+    Mail,
+    Link,
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+use spring::config::Configurable;
+
+#[derive(Clone, Deserialize, Configurable)]
+#[config_prefix = "pageview"]
+pub struct PageViewConfig {
+    /// 浏览量先累加在redis中，再由定时任务批量写入数据库
+    #[serde(default = "default_true")]
+    pub buffer: bool,
+    /// 同一访客在dedup_ttl秒内重复访问同一页面只计一次
+    #[serde(default)]
+    pub dedup: bool,
+    #[serde(default = "default_dedup_ttl")]
+    pub dedup_ttl: u64,
+    /// 不统计爬虫的访问
+    #[serde(default = "default_true")]
+    pub filter_crawlers: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_dedup_ttl() -> u64 {
+    30 * 60
+}
@@ -0,0 +1,30 @@
+use serde::Deserialize;
+use spring::config::Configurable;
+
+/// 页面路径的规范化规则，避免同一个页面因为url写法不同被拆分成多个页面
+#[derive(Clone, Deserialize, Configurable)]
+#[config_prefix = "path"]
+pub struct PathConfig {
+    /// 去掉末尾的`/`
+    #[serde(default = "default_true")]
+    pub trailing_slash: bool,
+    /// 去掉末尾的`index.html`、`index.htm`
+    #[serde(default = "default_true")]
+    pub strip_index: bool,
+    /// 保留的查询参数，其余参数都会被去掉
+    #[serde(default)]
+    pub allowed_query: Vec<String>,
+    /// 路径转成小写
+    #[serde(default)]
+    pub lowercase: bool,
+    /// 去掉`#`之后的部分
+    #[serde(default = "default_true")]
+    pub strip_fragment: bool,
+    /// 对路径做百分号解码
+    #[serde(default = "default_true")]
+    pub percent_decode: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
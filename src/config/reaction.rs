@@ -0,0 +1,24 @@
+use serde::Deserialize;
+use spring::config::Configurable;
+
+#[derive(Clone, Deserialize, Configurable)]
+#[config_prefix = "reaction"]
+pub struct ReactionConfig {
+    /// 评论可用的表情
+    #[serde(default = "default_comment_reactions")]
+    pub comment: Vec<String>,
+    /// 页面可用的表态，顺序对应Waline的reaction0..reaction8
+    #[serde(default = "default_page_reactions")]
+    pub page: Vec<String>,
+}
+
+fn default_comment_reactions() -> Vec<String> {
+    ["👍", "👎", "😄", "🎉", "😕", "❤️", "🚀", "👀"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn default_page_reactions() -> Vec<String> {
+    (0..9).map(|i| format!("reaction{i}")).collect()
+}
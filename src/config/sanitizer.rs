@@ -0,0 +1,179 @@
+use ammonia::Builder;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use spring::config::Configurable;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Serialize, Deserialize, Configurable)]
+#[config_prefix = "sanitizer"]
+pub struct SanitizerConfig {
+    /// 管理员发表的评论使用的策略
+    #[serde(default = "default_admin_policy")]
+    pub admin: SanitizerPolicy,
+    /// 其他用户和匿名访客发表的评论使用的策略
+    #[serde(default)]
+    pub anonymous: SanitizerPolicy,
+}
+
+impl SanitizerConfig {
+    pub fn policy(&self, admin: bool) -> &SanitizerPolicy {
+        if admin {
+            &self.admin
+        } else {
+            &self.anonymous
+        }
+    }
+
+    /// 启动时检查配置，冲突的配置会让ammonia在渲染每条评论时panic
+    pub fn check(&self) -> anyhow::Result<()> {
+        self.admin.check().context("invalid [sanitizer.admin]")?;
+        self.anonymous
+            .check()
+            .context("invalid [sanitizer.anonymous]")?;
+        Ok(())
+    }
+}
+
+/// 在ammonia默认白名单的基础上增删标签和属性
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SanitizerPolicy {
+    /// 额外允许的标签，如iframe、video
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    /// 禁止的标签，如img
+    #[serde(default)]
+    pub rm_tags: Vec<String>,
+    /// 所有标签都允许的属性，允许class会让访客可以使用站点的css class，只建议管理员使用
+    #[serde(default)]
+    pub generic_attributes: Vec<String>,
+    /// 指定标签允许的属性
+    #[serde(default)]
+    pub tag_attributes: BTreeMap<String, Vec<String>>,
+    /// 指定标签允许的class，不能同时在generic_attributes或tag_attributes中允许class
+    #[serde(default)]
+    pub allowed_classes: BTreeMap<String, Vec<String>>,
+    /// 允许的链接协议，不配置时使用ammonia的默认值
+    pub url_schemes: Option<Vec<String>>,
+    /// 链接上添加的rel属性，为空时不添加
+    #[serde(default = "default_link_rel")]
+    pub link_rel: String,
+    /// 图片代理地址，图片地址会以`url`参数拼接在后面
+    pub image_proxy: Option<String>,
+}
+
+impl Default for SanitizerPolicy {
+    fn default() -> Self {
+        Self {
+            add_tags: vec![],
+            rm_tags: vec![],
+            generic_attributes: vec![],
+            tag_attributes: BTreeMap::new(),
+            allowed_classes: BTreeMap::new(),
+            url_schemes: None,
+            link_rel: default_link_rel(),
+            image_proxy: None,
+        }
+    }
+}
+
+fn default_admin_policy() -> SanitizerPolicy {
+    let media = ["src", "width", "height", "controls", "poster"];
+    let tag_attributes = [
+        (
+            "iframe",
+            vec![
+                "src",
+                "width",
+                "height",
+                "allow",
+                "allowfullscreen",
+                "frameborder",
+            ],
+        ),
+        ("video", media.to_vec()),
+        ("audio", media.to_vec()),
+        ("source", vec!["src", "type"]),
+    ]
+    .into_iter()
+    .map(|(tag, attrs)| {
+        (
+            tag.to_string(),
+            attrs.into_iter().map(String::from).collect(),
+        )
+    })
+    .collect();
+    SanitizerPolicy {
+        add_tags: ["iframe", "video", "audio", "source"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        generic_attributes: vec!["class".to_string()],
+        tag_attributes,
+        link_rel: "noopener".to_string(),
+        ..Default::default()
+    }
+}
+
+fn default_link_rel() -> String {
+    "nofollow ugc noopener".to_string()
+}
+
+impl SanitizerPolicy {
+    /// 检查ammonia在clean时会assert的冲突配置
+    fn check(&self) -> anyhow::Result<()> {
+        let has = |attrs: Option<&Vec<String>>, attr: &str| {
+            attrs.is_some_and(|attrs| attrs.iter().any(|a| a == attr))
+        };
+        if !self.link_rel.is_empty()
+            && (has(Some(&self.generic_attributes), "rel")
+                || has(self.tag_attributes.get("a"), "rel"))
+        {
+            bail!("rel can't be allowed when link_rel is set");
+        }
+        if !self.allowed_classes.is_empty() && has(Some(&self.generic_attributes), "class") {
+            bail!("class can't be a generic attribute when allowed_classes is set");
+        }
+        for tag in self.allowed_classes.keys() {
+            if has(self.tag_attributes.get(tag), "class") {
+                bail!("class of {tag} can't be in both tag_attributes and allowed_classes");
+            }
+        }
+        // ammonia会连同内容一起删除的标签
+        for tag in ["script", "style"] {
+            if self.add_tags.iter().any(|t| t == tag) || self.tag_attributes.contains_key(tag) {
+                bail!("{tag} can't be allowed");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn builder(&self) -> Builder<'_> {
+        let mut builder = Builder::default();
+        builder
+            .add_tags(&self.add_tags)
+            .rm_tags(&self.rm_tags)
+            .add_generic_attributes(&self.generic_attributes)
+            .link_rel(Some(self.link_rel.as_str()).filter(|rel| !rel.is_empty()));
+        for (tag, attrs) in &self.tag_attributes {
+            builder.add_tag_attributes(tag.as_str(), attrs);
+        }
+        for (tag, classes) in &self.allowed_classes {
+            builder.add_allowed_classes(tag.as_str(), classes);
+        }
+        if let Some(schemes) = &self.url_schemes {
+            builder.url_schemes(schemes.iter().map(String::as_str).collect());
+        }
+        if let Some(proxy) = self.image_proxy.clone() {
+            builder.attribute_filter(move |element, attribute, value| {
+                let remote = value.starts_with("http://") || value.starts_with("https://");
+                if element != "img" || attribute != "src" || !remote {
+                    return Some(value.into());
+                }
+                let sep = if proxy.contains('?') { '&' } else { '?' };
+                let query = serde_urlencoded::to_string([("url", value)]).ok()?;
+                Some(format!("{proxy}{sep}{query}").into())
+            });
+        }
+        builder
+    }
+}
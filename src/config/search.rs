@@ -0,0 +1,23 @@
+use serde::Deserialize;
+use spring::config::Configurable;
+
+#[derive(Clone, Deserialize, Configurable)]
+#[config_prefix = "search"]
+pub struct SearchConfig {
+    /// 全文检索使用的text search configuration，需要和ddl.sql中search_vector的一致，
+    /// 安装zhparser并创建chinese配置后可以改为chinese以支持中文分词
+    #[serde(default = "default_ts_config")]
+    pub ts_config: String,
+    /// 同时用like匹配评论内容，默认关闭。simple配置无法检索没有空格分隔的中文词语，
+    /// 需要时可以开启，但like无法使用search_vector索引，建议配合pg_bigm索引使用
+    #[serde(default = "default_like_fallback")]
+    pub like_fallback: bool,
+}
+
+fn default_ts_config() -> String {
+    "simple".to_string()
+}
+
+fn default_like_fallback() -> bool {
+    false
+}
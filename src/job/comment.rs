@@ -0,0 +1,30 @@
+use crate::config::RalineConfig;
+use crate::service::comment::CommentService;
+use spring_job::extractor::{Component, Config};
+use spring_job::{cron, fix_rate};
+
+/// 每天凌晨3点清理过期的垃圾评论
+#[cron("0 0 3 * * *")]
+async fn purge_spam(
+    Component(comment_service): Component<CommentService>,
+    Config(config): Config<RalineConfig>,
+) {
+    let days = match config.spam_retention_days {
+        None => return,
+        Some(days) => days,
+    };
+    match comment_service.purge_spam(days).await {
+        Ok(count) => tracing::info!("purge {} spam comments older than {} days", count, days),
+        Err(e) => tracing::error!("purge spam comments failed: {:?}", e),
+    }
+}
+
+/// 每分钟重新渲染读取时发现html缓存过期的评论
+#[fix_rate(60)]
+async fn rebuild_stale_html(Component(comment_service): Component<CommentService>) {
+    match comment_service.rebuild_stale_html().await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("rebuild html of {} comments", count),
+        Err(e) => tracing::error!("rebuild comment html failed: {:?}", e),
+    }
+}
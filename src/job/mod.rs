@@ -0,0 +1,8 @@
+mod comment;
+mod pv_counter;
+
+use spring_job::{handler, Jobs};
+
+pub fn jobs() -> Jobs {
+    handler::auto_jobs()
+}
@@ -0,0 +1,18 @@
+use crate::service::pv_counter::PageViewService;
+use spring_job::extractor::Component;
+use spring_job::fix_rate;
+
+/// 每10秒将redis中缓冲的浏览量写入数据库
+#[fix_rate(10)]
+async fn flush_pageview(Component(pv_service): Component<PageViewService>) {
+    match pv_service.flush_buffer().await {
+        Ok(0) => {}
+        Ok(count) => tracing::debug!("flush view count of {} pages", count),
+        Err(e) => tracing::error!("flush view count failed: {:?}", e),
+    }
+    match pv_service.flush_stats().await {
+        Ok(0) => {}
+        Ok(count) => tracing::debug!("flush {} view stats", count),
+        Err(e) => tracing::error!("flush view stats failed: {:?}", e),
+    }
+}
@@ -1,4 +1,5 @@
 mod config;
+mod job;
 mod model;
 mod plugins;
 mod router;
@@ -6,8 +7,12 @@ mod service;
 mod utils;
 mod views;
 
-use plugins::{akismet::AkismetPlugin, ip2region::Ip2RegionPlugin, uaparser::UAParserPlugin};
+use plugins::{
+    akismet::AkismetPlugin, ip2region::Ip2RegionPlugin, sanitizer::SanitizerPlugin,
+    uaparser::UAParserPlugin,
+};
 use spring::App;
+use spring_job::{JobConfigurator, JobPlugin};
 use spring_mail::MailPlugin;
 use spring_opentelemetry::{
     KeyValue, OpenTelemetryPlugin, ResourceConfigurator, SERVICE_NAME, SERVICE_VERSION,
@@ -31,10 +36,13 @@ async fn main() {
         .add_plugin(SeaOrmPlugin)
         .add_plugin(MailPlugin)
         .add_plugin(RedisPlugin)
+        .add_plugin(JobPlugin)
         .add_plugin(AkismetPlugin)
         .add_plugin(UAParserPlugin)
         .add_plugin(Ip2RegionPlugin)
+        .add_plugin(SanitizerPlugin)
         .add_router(router::router())
+        .add_jobs(job::jobs())
         .run()
         .await
 }
@@ -4,7 +4,7 @@ use super::sea_orm_active_enums::CommentStatus;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "comments")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -18,12 +18,23 @@ pub struct Model {
     pub nick: Option<String>,
     pub pid: i32,
     pub rid: i32,
+    pub depth: i32,
     pub sticky: bool,
     pub status: CommentStatus,
     pub star: i32,
+    pub vote_down: i32,
     pub ip: String,
     #[sea_orm(column_type = "Text")]
     pub ua: String,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub moderation: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub mention: Option<Json>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub comment_html: Option<String>,
+    pub render_version: i64,
+    pub deleted_at: Option<DateTime>,
+    pub edited_at: Option<DateTime>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -2,8 +2,15 @@
 
 pub mod prelude;
 
+pub mod comment_reactions;
+pub mod comment_revisions;
+pub mod comment_votes;
 pub mod comments;
+pub mod page_aliases;
+pub mod page_reaction_voters;
+pub mod page_reactions;
 pub mod page_view_counter;
+pub mod page_view_stats;
 pub mod sea_orm_active_enums;
 pub mod user_oauth;
 pub mod users;
@@ -10,15 +10,9 @@ pub struct Model {
     pub id: i32,
     pub path: String,
     pub times: i32,
-    pub reaction0: i32,
-    pub reaction1: i32,
-    pub reaction2: i32,
-    pub reaction3: i32,
-    pub reaction4: i32,
-    pub reaction5: i32,
-    pub reaction6: i32,
-    pub reaction7: i32,
-    pub reaction8: i32,
+    pub title: Option<String>,
+    pub locked: bool,
+    pub hidden: bool,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -1,6 +1,13 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.0.1
 
+pub use super::comment_reactions::Entity as CommentReactions;
+pub use super::comment_revisions::Entity as CommentRevisions;
+pub use super::comment_votes::Entity as CommentVotes;
 pub use super::comments::Entity as Comments;
+pub use super::page_aliases::Entity as PageAliases;
+pub use super::page_reaction_voters::Entity as PageReactionVoters;
+pub use super::page_reactions::Entity as PageReactions;
 pub use super::page_view_counter::Entity as PageViewCounter;
+pub use super::page_view_stats::Entity as PageViewStats;
 pub use super::user_oauth::Entity as UserOauth;
 pub use super::users::Entity as Users;
@@ -0,0 +1,21 @@
+pub use super::_entities::comment_votes::*;
+
+use sea_orm::{sqlx::types::chrono::Local, ActiveModelBehavior, ConnectionTrait, DbErr, Set};
+use spring::async_trait;
+
+pub const UP: i16 = 1;
+pub const DOWN: i16 = -1;
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert {
+            self.created_at = Set(Local::now().naive_local());
+        }
+        self.updated_at = Set(Local::now().naive_local());
+        Ok(self)
+    }
+}
@@ -1,6 +1,11 @@
 pub use super::_entities::comments::*;
 
-use sea_orm::{sqlx::types::chrono::Local, ActiveModelBehavior, ConnectionTrait, DbErr, Set};
+use super::sea_orm_active_enums::CommentStatus;
+use sea_orm::{
+    sqlx::types::chrono::Local, ActiveModelBehavior, ColumnTrait, ConnectionTrait, DbErr,
+    EntityTrait, QueryFilter, QuerySelect, Set,
+};
+use serde::{Deserialize, Serialize};
 use spring::async_trait;
 
 pub fn root_comment_id() -> i32 {
@@ -20,3 +25,133 @@ impl ActiveModelBehavior for ActiveModel {
         Ok(self)
     }
 }
+
+/// 审核结论，记录评论被判定为spam或waiting的原因
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationVerdict {
+    pub stage: ModerationStage,
+    pub rule: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationStage {
+    /// 通过了所有检查
+    Passed,
+    /// 开启了人工审核
+    Audit,
+    /// Akismet判定为垃圾评论
+    Akismet,
+    /// 命中了forbidden_words
+    Keyword,
+}
+
+impl ModerationVerdict {
+    pub fn new(stage: ModerationStage) -> Self {
+        Self { stage, rule: None }
+    }
+}
+
+/// 回复时@的评论，展示时渲染成`[@nick](#pid): `
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mention {
+    pub pid: i32,
+    pub nick: String,
+}
+
+impl Mention {
+    pub fn to_markdown(&self) -> String {
+        let nick = self.nick.replace('[', "\\[").replace(']', "\\]");
+        format!("[@{nick}](#{}): ", self.pid)
+    }
+}
+
+impl Model {
+    pub fn moderation_verdict(&self) -> Option<ModerationVerdict> {
+        self.moderation
+            .clone()
+            .and_then(|v| serde_json::from_value(v).ok())
+    }
+
+    pub fn mention(&self) -> Option<Mention> {
+        self.mention
+            .clone()
+            .and_then(|v| serde_json::from_value(v).ok())
+    }
+}
+
+/// 未删除评论按状态的数量
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatusCount {
+    pub approved: u64,
+    pub waiting: u64,
+    pub spam: u64,
+}
+
+impl Entity {
+    /// 用一条group by查询统计各个状态的评论数
+    pub async fn count_by_status<C>(db: &C) -> Result<StatusCount, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let counts: Vec<(CommentStatus, i64)> = Entity::find()
+            .select_only()
+            .column(Column::Status)
+            .column_as(Column::Id.count(), "count")
+            .filter(Column::DeletedAt.is_null())
+            .group_by(Column::Status)
+            .into_tuple()
+            .all(db)
+            .await?;
+        let mut result = StatusCount::default();
+        for (status, count) in counts {
+            let count = count as u64;
+            match status {
+                CommentStatus::Approved => result.approved = count,
+                CommentStatus::Waiting => result.waiting = count,
+                CommentStatus::Spam => result.spam = count,
+            }
+        }
+        Ok(result)
+    }
+
+    /// 彻底删除评论及其所有回复，返回删除的评论数
+    pub async fn purge<C>(db: &C, ids: Vec<i32>) -> Result<u64, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let mut all_ids = ids.clone();
+        let mut parents = ids;
+        while !parents.is_empty() {
+            let children: Vec<i32> = Entity::find()
+                .select_only()
+                .column(Column::Id)
+                .filter(Column::Pid.is_in(parents))
+                .into_tuple()
+                .all(db)
+                .await?;
+            parents = children
+                .into_iter()
+                .filter(|id| !all_ids.contains(id))
+                .collect();
+            all_ids.extend(&parents);
+        }
+        super::comment_revisions::Entity::delete_many()
+            .filter(super::comment_revisions::Column::CommentId.is_in(all_ids.clone()))
+            .exec(db)
+            .await?;
+        super::comment_votes::Entity::delete_many()
+            .filter(super::comment_votes::Column::CommentId.is_in(all_ids.clone()))
+            .exec(db)
+            .await?;
+        super::comment_reactions::Entity::delete_many()
+            .filter(super::comment_reactions::Column::CommentId.is_in(all_ids.clone()))
+            .exec(db)
+            .await?;
+        let result = Entity::delete_many()
+            .filter(Column::Id.is_in(all_ids))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+}
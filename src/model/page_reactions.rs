@@ -0,0 +1,5 @@
+pub use super::_entities::page_reactions::*;
+
+use sea_orm::ActiveModelBehavior;
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,14 +1,17 @@
-use std::{cmp::max, collections::HashMap};
+use std::collections::HashMap;
 
 pub use super::_entities::page_view_counter::*;
-use crate::views::pv_counter::{ColumnQueryAs, SetCountAction, SetViewCount};
+use super::page_view_stats::StatDimension;
+use super::{comments, page_aliases, page_reaction_voters, page_reactions, page_view_stats};
 use itertools::Itertools;
+use sea_orm::sea_query::{Alias, Expr, OnConflict};
 use sea_orm::{
     sqlx::types::chrono::Local, ActiveModelBehavior, ActiveModelTrait, ColumnTrait,
-    ConnectionTrait, DbConn, DbErr, DerivePartialModel, EntityTrait, FromQueryResult, QueryFilter,
-    Set,
+    ConnectionTrait, DbErr, DerivePartialModel, EntityTrait, FromQueryResult, IntoActiveModel,
+    QueryFilter, Set,
 };
 use spring::async_trait;
+use std::str::FromStr;
 
 #[async_trait]
 impl ActiveModelBehavior for ActiveModel {
@@ -25,17 +28,40 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 impl Entity {
+    /// 根据路径查找页面，找不到时再按别名查找，返回的path为页面当前的路径
     pub async fn find_id_by_path<C, S>(db: &C, path: S) -> Result<Option<PathId>, DbErr>
     where
         C: ConnectionTrait,
         S: Into<String>,
     {
+        let path = path.into();
         let path_id = Entity::find()
-            .filter(Column::Path.eq(path.into()))
+            .filter(Column::Path.eq(path.as_str()))
             .into_partial_model::<PathId>()
             .one(db)
             .await?;
-        Ok(path_id)
+        if path_id.is_some() {
+            return Ok(path_id);
+        }
+        let alias = page_aliases::Entity::find_by_id(path).one(db).await?;
+        let Some(alias) = alias else {
+            return Ok(None);
+        };
+        Entity::find_by_id(alias.page_id)
+            .into_partial_model::<PathId>()
+            .one(db)
+            .await
+    }
+
+    /// 同find_id_by_path，返回完整的页面信息
+    pub async fn find_by_path<C>(db: &C, path: &str) -> Result<Option<Model>, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        match Self::find_id_by_path(db, path).await? {
+            Some(page) => Entity::find_by_id(page.id).one(db).await,
+            None => Ok(None),
+        }
     }
 
     pub async fn find_ids_by_paths<C, V, S>(
@@ -49,68 +75,199 @@ impl Entity {
     {
         let paths: Vec<String> = paths.into_iter().map(|s| s.to_string()).collect_vec();
         let path_ids = Entity::find()
-            .filter(Column::Path.is_in(paths))
+            .filter(Column::Path.is_in(paths.clone()))
             .into_partial_model::<PathId>()
             .all(db)
             .await?;
-        Ok(path_ids.into_iter().map(|p| (p.path, p.id)).collect())
+        let mut path_ids: HashMap<String, i32> =
+            path_ids.into_iter().map(|p| (p.path, p.id)).collect();
+        let missing = paths
+            .into_iter()
+            .filter(|p| !path_ids.contains_key(p))
+            .collect_vec();
+        if !missing.is_empty() {
+            let aliases = page_aliases::Entity::find()
+                .filter(page_aliases::Column::Path.is_in(missing))
+                .all(db)
+                .await?;
+            path_ids.extend(aliases.into_iter().map(|a| (a.path, a.page_id)));
+        }
+        Ok(path_ids)
     }
 
-    pub async fn increase_by_path(db: &DbConn, q: &SetViewCount) -> Result<Model, DbErr> {
-        let model = Entity::find()
-            .filter(Column::Path.eq(&q.path))
-            .one(db)
-            .await?;
+    pub async fn find_or_create_id_by_path<C>(db: &C, path: &str) -> Result<i32, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if let Some(page) = Self::find_id_by_path(db, path).await? {
+            return Ok(page.id);
+        }
+        // 并发创建同一个页面时由唯一索引兜底，冲突时返回已存在的页面
+        let page = Entity::insert(ActiveModel {
+            path: Set(path.to_string()),
+            ..Default::default()
+        })
+        .on_conflict(site_path_conflict().update_column(Column::Path).to_owned())
+        .exec_with_returning(db)
+        .await?;
+        Ok(page.id)
+    }
 
-        let model = match model {
-            None => {
-                let mut am = ActiveModel {
-                    path: Set(q.path.clone()),
-                    ..Default::default()
-                };
-                match q.r#type {
-                    ColumnQueryAs::Times => am.times = Set(1),
-                    ColumnQueryAs::Reaction0 => am.reaction0 = Set(1),
-                    ColumnQueryAs::Reaction1 => am.reaction1 = Set(1),
-                    ColumnQueryAs::Reaction2 => am.reaction2 = Set(1),
-                    ColumnQueryAs::Reaction3 => am.reaction3 = Set(1),
-                    ColumnQueryAs::Reaction4 => am.reaction4 = Set(1),
-                    ColumnQueryAs::Reaction5 => am.reaction5 = Set(1),
-                    ColumnQueryAs::Reaction6 => am.reaction6 = Set(1),
-                    ColumnQueryAs::Reaction7 => am.reaction7 = Set(1),
-                    ColumnQueryAs::Reaction8 => am.reaction8 = Set(1),
-                };
-                am.insert(db).await?
-            }
-            Some(m) => {
-                let mut am = ActiveModel {
-                    id: Set(m.id),
-                    ..Default::default()
-                };
-                let delta = match q.action {
-                    SetCountAction::Asc => 1,
-                    SetCountAction::Desc => -1,
-                };
-                match q.r#type {
-                    ColumnQueryAs::Times => am.times = Set(max(m.times + delta, 0)),
-                    ColumnQueryAs::Reaction0 => am.reaction0 = Set(max(m.reaction0 + delta, 0)),
-                    ColumnQueryAs::Reaction1 => am.reaction1 = Set(max(m.reaction1 + delta, 0)),
-                    ColumnQueryAs::Reaction2 => am.reaction2 = Set(max(m.reaction2 + delta, 0)),
-                    ColumnQueryAs::Reaction3 => am.reaction3 = Set(max(m.reaction3 + delta, 0)),
-                    ColumnQueryAs::Reaction4 => am.reaction4 = Set(max(m.reaction4 + delta, 0)),
-                    ColumnQueryAs::Reaction5 => am.reaction5 = Set(max(m.reaction5 + delta, 0)),
-                    ColumnQueryAs::Reaction6 => am.reaction6 = Set(max(m.reaction6 + delta, 0)),
-                    ColumnQueryAs::Reaction7 => am.reaction7 = Set(max(m.reaction7 + delta, 0)),
-                    ColumnQueryAs::Reaction8 => am.reaction8 = Set(max(m.reaction8 + delta, 0)),
-                };
-                am.update(db).await?
+    /// 查找或创建页面，传入了标题时同时更新页面标题，锁定的页面不更新标题
+    pub async fn find_or_create_page<C>(
+        db: &C,
+        path: &str,
+        title: Option<&str>,
+    ) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let id = Self::find_or_create_id_by_path(db, path).await?;
+        let page = Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::RecordNotFound(format!("page {id} not found")))?;
+        let title = title
+            .map(|t| t.trim().chars().take(255).collect::<String>())
+            .filter(|t| !t.is_empty());
+        match title {
+            Some(title) if !page.locked && page.title.as_ref() != Some(&title) => {
+                let mut page = page.into_active_model();
+                page.title = Set(Some(title));
+                page.update(db).await
             }
+            _ => Ok(page),
+        }
+    }
+
+    /// 原子地增加浏览量：`insert ... on conflict do update set times = times + delta returning *`
+    pub async fn increase_by_path<C>(db: &C, path: &str, delta: i32) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        // 别名的浏览量累加到别名指向的页面上
+        let path = match Self::find_id_by_path(db, path).await? {
+            Some(page) => page.path,
+            None => path.to_string(),
         };
+        Entity::insert(ActiveModel {
+            path: Set(path),
+            times: Set(delta),
+            ..Default::default()
+        })
+        .on_conflict(
+            site_path_conflict()
+                .value(Column::Times, Expr::col((Entity, Column::Times)).add(delta))
+                .value(Column::UpdatedAt, Expr::current_timestamp())
+                .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await
+    }
+
+    /// 把source页面的评论、表态和浏览统计合并到target页面，并删除source页面
+    pub async fn merge_pages<C>(db: &C, target: i32, sources: &[i32]) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if sources.is_empty() {
+            return Ok(());
+        }
+        comments::Entity::update_many()
+            .col_expr(comments::Column::PageId, Expr::value(target))
+            .filter(comments::Column::PageId.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
 
-        Ok(model)
+        let reactions = page_reactions::Entity::find()
+            .filter(page_reactions::Column::PageId.is_in(sources.to_vec()))
+            .all(db)
+            .await?;
+        for r in reactions {
+            page_reactions::Entity::insert(page_reactions::ActiveModel {
+                page_id: Set(target),
+                reaction_key: Set(r.reaction_key),
+                count: Set(r.count),
+            })
+            .on_conflict(
+                OnConflict::columns([
+                    page_reactions::Column::PageId,
+                    page_reactions::Column::ReactionKey,
+                ])
+                .value(
+                    page_reactions::Column::Count,
+                    Expr::col((page_reactions::Entity, page_reactions::Column::Count)).add(r.count),
+                )
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        }
+        let voters = page_reaction_voters::Entity::find()
+            .filter(page_reaction_voters::Column::PageId.is_in(sources.to_vec()))
+            .all(db)
+            .await?;
+        for v in voters {
+            page_reaction_voters::Entity::insert(page_reaction_voters::ActiveModel {
+                page_id: Set(target),
+                reaction_key: Set(v.reaction_key),
+                voter: Set(v.voter),
+                created_at: Set(v.created_at),
+            })
+            .on_conflict(
+                OnConflict::columns([
+                    page_reaction_voters::Column::PageId,
+                    page_reaction_voters::Column::ReactionKey,
+                    page_reaction_voters::Column::Voter,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+        }
+
+        let stats = page_view_stats::Entity::find()
+            .filter(page_view_stats::Column::PageId.is_in(sources.to_vec()))
+            .all(db)
+            .await?;
+        for s in stats {
+            let Ok(dimension) = StatDimension::from_str(&s.dimension) else {
+                continue;
+            };
+            page_view_stats::Entity::increase(db, target, s.day, dimension, &s.value, s.times)
+                .await?;
+        }
+
+        page_reactions::Entity::delete_many()
+            .filter(page_reactions::Column::PageId.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
+        page_reaction_voters::Entity::delete_many()
+            .filter(page_reaction_voters::Column::PageId.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
+        page_aliases::Entity::update_many()
+            .col_expr(page_aliases::Column::PageId, Expr::value(target))
+            .filter(page_aliases::Column::PageId.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
+        page_view_stats::Entity::delete_many()
+            .filter(page_view_stats::Column::PageId.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
+        Entity::delete_many()
+            .filter(Column::Id.is_in(sources.to_vec()))
+            .exec(db)
+            .await?;
+        Ok(())
     }
 }
 
+fn site_path_conflict() -> OnConflict {
+    OnConflict::columns([Alias::new("site_id"), Alias::new("path")])
+}
+
 #[derive(DerivePartialModel, FromQueryResult)]
 #[sea_orm(entity = "Entity")]
 pub struct PathId {
@@ -119,3 +276,85 @@ pub struct PathId {
     #[sea_orm(from_col = "path")]
     pub path: String,
 }
+
+/// 需要已经执行过ddl.sql的Postgres：`TEST_DATABASE_URL=postgres://... cargo test -- --ignored`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{Database, DatabaseConnection, PaginatorTrait};
+
+    const N: i32 = 32;
+
+    async fn connect() -> DatabaseConnection {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+        Database::connect(url)
+            .await
+            .expect("connect database failed")
+    }
+
+    fn test_path(name: &str) -> String {
+        format!(
+            "/__test__/{name}/{}",
+            Local::now().timestamp_nanos_opt().unwrap()
+        )
+    }
+
+    async fn cleanup(db: &DatabaseConnection, path: &str) {
+        Entity::delete_many()
+            .filter(Column::Path.eq(path))
+            .exec(db)
+            .await
+            .expect("cleanup failed");
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn concurrent_increase_by_path() {
+        let db = connect().await;
+        let path = test_path("increase");
+        let tasks = (0..N)
+            .map(|_| {
+                let (db, path) = (db.clone(), path.clone());
+                tokio::spawn(async move { Entity::increase_by_path(&db, &path, 1).await })
+            })
+            .collect_vec();
+        for task in tasks {
+            task.await.unwrap().expect("increase failed");
+        }
+
+        let pages = Entity::find()
+            .filter(Column::Path.eq(path.as_str()))
+            .all(&db)
+            .await
+            .unwrap();
+        cleanup(&db, &path).await;
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].times, N);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn concurrent_find_or_create() {
+        let db = connect().await;
+        let path = test_path("create");
+        let tasks = (0..N)
+            .map(|_| {
+                let (db, path) = (db.clone(), path.clone());
+                tokio::spawn(async move { Entity::find_or_create_id_by_path(&db, &path).await })
+            })
+            .collect_vec();
+        let mut ids = vec![];
+        for task in tasks {
+            ids.push(task.await.unwrap().expect("find or create failed"));
+        }
+
+        let count = Entity::find()
+            .filter(Column::Path.eq(path.as_str()))
+            .count(&db)
+            .await
+            .unwrap();
+        cleanup(&db, &path).await;
+        assert_eq!(count, 1);
+        assert!(ids.iter().all_equal());
+    }
+}
@@ -0,0 +1,67 @@
+pub use super::_entities::page_view_stats::*;
+
+use sea_orm::prelude::Date;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ActiveModelBehavior, ConnectionTrait, DbErr, EntityTrait, Set};
+use serde::{Deserialize, Serialize};
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 浏览量的统计维度，`total`只有一个空value，用于统计每天的总浏览量
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum StatDimension {
+    Total,
+    Referrer,
+    Country,
+    Browser,
+    Os,
+}
+
+impl Entity {
+    /// 原子地累加某个页面某天某个维度的浏览量
+    pub async fn increase<C>(
+        db: &C,
+        page_id: i32,
+        day: Date,
+        dimension: StatDimension,
+        value: &str,
+        delta: i32,
+    ) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        Entity::insert(ActiveModel {
+            page_id: Set(page_id),
+            day: Set(day),
+            dimension: Set(dimension.as_ref().to_string()),
+            value: Set(value.to_string()),
+            times: Set(delta),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                Column::PageId,
+                Column::Day,
+                Column::Dimension,
+                Column::Value,
+            ])
+            .value(Column::Times, Expr::col((Entity, Column::Times)).add(delta))
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        Ok(())
+    }
+}
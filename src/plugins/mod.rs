@@ -1,3 +1,4 @@
 pub mod akismet;
+pub mod ip2region;
+pub mod sanitizer;
 pub mod uaparser;
-pub mod ip2region;
\ No newline at end of file
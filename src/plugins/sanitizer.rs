@@ -0,0 +1,20 @@
+use crate::config::sanitizer::SanitizerConfig;
+use spring::app::AppBuilder;
+use spring::async_trait;
+use spring::config::ConfigRegistry;
+use spring::plugin::Plugin;
+
+/// 启动时检查sanitizer配置，避免冲突的配置在渲染评论时才panic
+pub struct SanitizerPlugin;
+
+#[async_trait]
+impl Plugin for SanitizerPlugin {
+    async fn build(&self, app: &mut AppBuilder) {
+        let config = app
+            .get_config::<SanitizerConfig>()
+            .expect("sanitizer config load failed");
+        if let Err(e) = config.check() {
+            panic!("{:?}", e);
+        }
+    }
+}
@@ -1,10 +1,20 @@
 use delegate_attr::delegate;
+use lazy_static::lazy_static;
+use regex::Regex;
 use spring::{app::AppBuilder, async_trait, plugin::Plugin};
 use std::sync::Arc;
 use uaparser::{Parser, UserAgent, UserAgentParser, OS};
 
 pub struct UAParserPlugin;
 
+lazy_static! {
+    /// 爬虫UA的特征：`Googlebot/2.1`、`DuckDuckBot-Https`、`bot`单词、`spider`、`crawler`，
+    /// 不匹配`Cubot`这类只是以bot结尾的手机型号
+    static ref CRAWLER_REGEX: Regex =
+        Regex::new(r"(?i)bot[/\-]|\bbot\b|spider|crawler|facebookexternalhit")
+            .expect("regex parse failed");
+}
+
 #[async_trait]
 impl Plugin for UAParserPlugin {
     async fn build(&self, app: &mut AppBuilder) {
@@ -28,6 +38,21 @@ impl Parser for UAParser {
     fn parse_user_agent<'a>(&self, user_agent: &'a str) -> uaparser::UserAgent<'a> {}
 }
 
+impl UAParser {
+    /// 通过device family("Spider")、浏览器family和UA中的爬虫特征识别爬虫
+    pub fn is_crawler(&self, user_agent: &str) -> bool {
+        if user_agent.is_empty() {
+            return true;
+        }
+        let client = self.parse(user_agent);
+        if client.device.family == "Spider" {
+            return true;
+        }
+        let family = client.user_agent.family.to_lowercase();
+        family.ends_with("bot") || CRAWLER_REGEX.is_match(user_agent)
+    }
+}
+
 pub trait ToStringExt {
     fn to_string(&self) -> String;
 }
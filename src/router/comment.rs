@@ -1,13 +1,19 @@
-use crate::views::comment::{AddCommentReq, CommentQueryResp, CommentUpdateReq};
-use crate::router::Locale;
 use crate::model::sea_orm_active_enums::UserType;
 use crate::model::{comments, prelude::*};
+use crate::router::Locale;
 use crate::service::comment::CommentService;
-use crate::{views::comment::CommentQueryReq, utils::jwt::OptionalClaims};
+use crate::utils::jwt::Claims;
+use crate::utils::visitor::Visitor;
+use crate::views::comment::{
+    AddCommentReq, BatchCommentReq, CommentQueryResp, CommentReactionQuery, CommentUpdateReq,
+    PreviewCommentReq, PurgeSpamReq, ReactionReq, RebuildHtmlReq, SearchCommentQuery, VoteReq,
+};
+use crate::{utils::jwt::OptionalClaims, views::comment::CommentQueryReq};
 use anyhow::Context;
 use axum_client_ip::SecureClientIp;
 use rust_i18n::t;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::sqlx::types::chrono::Local;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
 use serde_json::json;
 use spring_sea_orm::DbConn;
 use spring_web::delete;
@@ -18,20 +24,25 @@ use spring_web::{
     extractor::{Component, Path, Query},
     get, post, put,
 };
+use validator::Validate;
 
 #[get("/api/comment")]
 async fn get_comment(
     claims: OptionalClaims,
     Component(user_service): Component<CommentService>,
+    visitor: Visitor,
     Query(req): Query<CommentQueryReq>,
+    Locale(lang): Locale,
 ) -> Result<Json<CommentQueryResp>> {
+    req.validate()
+        .map_err(|e| KnownWebError::bad_request(e.to_string()))?;
     match req {
         CommentQueryReq::Count(q) => user_service
             .get_comment_count(&q, &claims)
             .await
             .map(|r| Json(r.into())),
         CommentQueryReq::List(q) => user_service
-            .get_comment_list(&q, &claims)
+            .get_comment_list(&q, &claims, &visitor, &lang)
             .await
             .map(|r| Json(r.into())),
         CommentQueryReq::Admin(q) => user_service
@@ -39,7 +50,7 @@ async fn get_comment(
             .await
             .map(|r| Json(r.into())),
         CommentQueryReq::Recent(q) => user_service
-            .get_recent_comment_list(&q, &claims)
+            .get_recent_comment_list(&q, &claims, &visitor)
             .await
             .map(|r| Json(r.into())),
     }
@@ -50,26 +61,148 @@ async fn add_comment(
     claims: OptionalClaims,
     Component(comment_service): Component<CommentService>,
     SecureClientIp(client_ip): SecureClientIp,
+    Locale(lang): Locale,
     Json(body): Json<AddCommentReq>,
 ) -> Result<impl IntoResponse> {
-    let comment = comment_service.add_comment(claims, client_ip, body).await?;
+    let comment = comment_service
+        .add_comment(claims, client_ip, body, &lang)
+        .await?;
     Ok(Json(json!({"data": comment})))
 }
 
+#[post("/api/comment/preview")]
+async fn preview_comment(
+    claims: OptionalClaims,
+    Component(comment_service): Component<CommentService>,
+    SecureClientIp(client_ip): SecureClientIp,
+    Locale(lang): Locale,
+    Json(body): Json<PreviewCommentReq>,
+) -> Result<impl IntoResponse> {
+    let html = comment_service
+        .preview_comment(&claims, client_ip, &body, &lang)
+        .await?;
+    Ok(Json(json!({"data": {"html": html}})))
+}
+
 #[put("/api/comment/:id")]
 async fn update_comment(
     optional_claims: OptionalClaims,
+    visitor: Visitor,
     Component(comment_service): Component<CommentService>,
     Path(id): Path<i32>,
+    Locale(lang): Locale,
     Json(body): Json<CommentUpdateReq>,
 ) -> Result<impl IntoResponse> {
     let comment = comment_service
-        .update_comment(optional_claims, id, body)
+        .update_comment(optional_claims, &visitor, id, body, &lang)
         .await?;
 
     Ok(Json(json!({"data": comment})))
 }
 
+#[post("/api/comment/:id/vote")]
+async fn vote_comment(
+    visitor: Visitor,
+    Component(comment_service): Component<CommentService>,
+    Path(id): Path<i32>,
+    Json(body): Json<VoteReq>,
+) -> Result<impl IntoResponse> {
+    comment_service
+        .vote_comment(&visitor, id, body.vote.value())
+        .await?;
+    Ok(Json(json!({"data": true})))
+}
+
+#[get("/api/comment/:id/revisions")]
+async fn get_comment_revisions(
+    claims: Claims,
+    Component(comment_service): Component<CommentService>,
+    Path(id): Path<i32>,
+    Locale(lang): Locale,
+) -> Result<impl IntoResponse> {
+    let revisions = comment_service
+        .get_comment_revisions(&claims, id, &lang)
+        .await?;
+    Ok(Json(json!({"data": revisions})))
+}
+
+#[post("/api/comment/:id/reaction")]
+async fn toggle_reaction(
+    visitor: Visitor,
+    Component(comment_service): Component<CommentService>,
+    Path(id): Path<i32>,
+    Json(body): Json<ReactionReq>,
+) -> Result<impl IntoResponse> {
+    let reactions = comment_service
+        .toggle_reaction(&visitor, id, &body.reaction)
+        .await?;
+    Ok(Json(json!({"data": reactions})))
+}
+
+#[get("/api/comment/search")]
+async fn search_comment(
+    claims: OptionalClaims,
+    Component(comment_service): Component<CommentService>,
+    Query(q): Query<SearchCommentQuery>,
+) -> Result<impl IntoResponse> {
+    q.validate()
+        .map_err(|e| KnownWebError::bad_request(e.to_string()))?;
+    let hits = comment_service.search_comments(&q, &claims).await?;
+    Ok(Json(json!({"data": hits})))
+}
+
+#[get("/api/comment/reaction")]
+async fn get_page_reactions(
+    visitor: Visitor,
+    Component(comment_service): Component<CommentService>,
+    Query(q): Query<CommentReactionQuery>,
+) -> Result<impl IntoResponse> {
+    let reactions = comment_service.get_page_reactions(&q, &visitor).await?;
+    Ok(Json(json!({"data": reactions})))
+}
+
+#[post("/api/comment/batch")]
+async fn batch_update_comment(
+    claims: OptionalClaims,
+    Component(comment_service): Component<CommentService>,
+    Json(body): Json<BatchCommentReq>,
+) -> Result<impl IntoResponse> {
+    body.validate()
+        .map_err(|e| KnownWebError::bad_request(e.to_string()))?;
+    let affected = comment_service.batch_update_comments(&claims, body).await?;
+    Ok(Json(json!({"data": affected})))
+}
+
+#[post("/api/comment/purge-spam")]
+async fn purge_spam(
+    claims: OptionalClaims,
+    Component(comment_service): Component<CommentService>,
+    Locale(lang): Locale,
+    Json(body): Json<PurgeSpamReq>,
+) -> Result<impl IntoResponse> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+    body.validate()
+        .map_err(|e| KnownWebError::bad_request(e.to_string()))?;
+    let affected = comment_service.purge_spam(body.days).await?;
+    Ok(Json(json!({"data": affected})))
+}
+
+#[post("/api/comment/rebuild-html")]
+async fn rebuild_html(
+    claims: OptionalClaims,
+    Component(comment_service): Component<CommentService>,
+    Locale(lang): Locale,
+    Json(body): Json<RebuildHtmlReq>,
+) -> Result<impl IntoResponse> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+    let rebuilt = comment_service.rebuild_html(body.force).await?;
+    Ok(Json(json!({"data": rebuilt})))
+}
+
 #[delete("/api/comment/:id")]
 async fn delete_comment(
     claims: OptionalClaims,
@@ -92,16 +225,66 @@ async fn delete_comment(
         Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
     }
 
-    let effect = Comments::delete_many()
+    let success = match c.deleted_at {
+        Some(_) => false,
+        None => {
+            comments::ActiveModel {
+                id: Set(c.id),
+                deleted_at: Set(Some(Local::now().naive_local())),
+                ..Default::default()
+            }
+            .update(&db)
+            .await
+            .context("delete comment failed")?;
+            true
+        }
+    };
+    Ok(Json(json!({"data":success})))
+}
+
+#[put("/api/comment/:id/restore")]
+async fn restore_comment(
+    claims: OptionalClaims,
+    Component(db): Component<DbConn>,
+    Path(id): Path<i32>,
+    Locale(lang): Locale,
+) -> Result<impl IntoResponse> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+
+    let effect = Comments::update_many()
+        .set(comments::ActiveModel {
+            deleted_at: Set(None),
+            ..Default::default()
+        })
         .filter(
             comments::Column::Id
                 .eq(id)
-                .or(comments::Column::Pid.eq(id))
-                .or(comments::Column::Rid.eq(id)),
+                .and(comments::Column::DeletedAt.is_not_null()),
         )
         .exec(&db)
         .await
-        .context("delete comment failed")?;
+        .context("restore comment failed")?;
     let success = effect.rows_affected > 0;
     Ok(Json(json!({"data":success})))
 }
+
+#[delete("/api/comment/:id/purge")]
+async fn purge_comment(
+    claims: OptionalClaims,
+    Component(db): Component<DbConn>,
+    Path(id): Path<i32>,
+    Locale(lang): Locale,
+) -> Result<impl IntoResponse> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+
+    let txn = db.begin().await.context("begin transaction failed")?;
+    let purged = Comments::purge(&txn, vec![id])
+        .await
+        .context("purge comment failed")?;
+    txn.commit().await.context("commit transaction failed")?;
+    Ok(Json(json!({"data": purged > 0})))
+}
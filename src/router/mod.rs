@@ -1,6 +1,8 @@
 mod comment;
 mod oauth;
+mod page;
 mod pv_counter;
+mod stats;
 mod token;
 mod user;
 
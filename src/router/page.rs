@@ -0,0 +1,105 @@
+use crate::model::sea_orm_active_enums::UserType;
+use crate::router::Locale;
+use crate::service::page::PageService;
+use crate::utils::jwt::OptionalClaims;
+use crate::views::page::{MergePageReq, PageAliasQuery, PageAliasReq, PageMetaReq, RenamePageReq};
+use rust_i18n::t;
+use serde_json::json;
+use spring_web::{
+    axum::{response::IntoResponse, Json},
+    delete,
+    error::{KnownWebError, Result},
+    extractor::{Component, Query},
+    get, post, put,
+};
+
+/// 修改了`[path]`配置后，合并已有的重复页面
+#[post("/api/page/normalize")]
+async fn normalize_pages(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let merged = page_service.normalize_pages().await?;
+    Ok(Json(json!({"data": merged})))
+}
+
+#[put("/api/page/rename")]
+async fn rename_page(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Json(body): Json<RenamePageReq>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let page = page_service.rename_page(&body).await?;
+    Ok(Json(json!({"data": page})))
+}
+
+#[put("/api/page/meta")]
+async fn update_page_meta(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Json(body): Json<PageMetaReq>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let page = page_service.update_meta(&body).await?;
+    Ok(Json(json!({"data": page})))
+}
+
+#[post("/api/page/merge")]
+async fn merge_page(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Json(body): Json<MergePageReq>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let page = page_service.merge_page(&body).await?;
+    Ok(Json(json!({"data": page})))
+}
+
+#[get("/api/page/alias")]
+async fn get_page_aliases(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Query(q): Query<PageAliasQuery>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let aliases = page_service.get_aliases(&q.path).await?;
+    Ok(Json(json!({"data": aliases})))
+}
+
+#[post("/api/page/alias")]
+async fn add_page_alias(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Json(body): Json<PageAliasReq>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    page_service.add_alias(&body).await?;
+    Ok(Json(json!({"data": true})))
+}
+
+#[delete("/api/page/alias")]
+async fn remove_page_alias(
+    claims: OptionalClaims,
+    Component(page_service): Component<PageService>,
+    Locale(lang): Locale,
+    Query(q): Query<PageAliasQuery>,
+) -> Result<impl IntoResponse> {
+    check_admin(&claims, &lang)?;
+    let affected = page_service.remove_alias(&q.path).await?;
+    Ok(Json(json!({"data": affected})))
+}
+
+fn check_admin(claims: &OptionalClaims, lang: &str) -> Result<()> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+    Ok(())
+}
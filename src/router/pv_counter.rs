@@ -1,74 +1,92 @@
-use crate::views::pv_counter::{ColumnQueryAs, SetViewCount};
-use crate::model::prelude::PageViewCounter;
-use crate::{views::pv_counter::ViewCountQuery, model::page_view_counter};
-use anyhow::Context;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use crate::model::page_view_stats::StatDimension;
+use crate::model::sea_orm_active_enums::UserType;
+use crate::router::Locale;
+use crate::service::pv_counter::PageViewService;
+use crate::utils::jwt::OptionalClaims;
+use crate::utils::visitor::{PageVisit, Visitor};
+use crate::views::pv_counter::{CounterType, SetViewCount, StatsQuery, ViewCountQuery};
+use rust_i18n::t;
 use serde_json::json;
-use spring_sea_orm::DbConn;
 use spring_web::{
     axum::{response::IntoResponse, Json},
-    error::Result,
-    extractor::{Component, Query},
+    error::{KnownWebError, Result},
+    extractor::{Component, Path, Query},
     get, post,
 };
 use std::collections::HashMap;
 
 #[get("/api/view")]
 async fn get_view_count(
-    Component(db): Component<DbConn>,
+    Component(pv_service): Component<PageViewService>,
     Query(req): Query<ViewCountQuery>,
 ) -> Result<impl IntoResponse> {
-    if req.path.is_empty() {
-        let result = req.types.into_iter().map(|ty| (ty, 0)).collect();
-        return Ok(Json(vec![result]));
-    }
-    let result = PageViewCounter::find()
-        .filter(page_view_counter::Column::Path.is_in(&req.path))
-        .all(&db)
-        .await
-        .context("query view counter failed")?;
-
-    let item_map: HashMap<String, page_view_counter::Model> =
-        result.into_iter().map(|vc| (vc.path.clone(), vc)).collect();
-
-    let ty_count: usize = req.types.len();
-    let mut result = vec![];
-    for url in req.path {
-        let mut counter = HashMap::<ColumnQueryAs, i32>::with_capacity(ty_count);
-        for field in &req.types {
-            counter.insert(
-                field.clone(),
-                item_map
-                    .get(&url)
-                    .map(|vc| field.get(vc))
-                    .unwrap_or_default(),
-            );
-        }
-        result.push(counter);
-    }
+    let result = pv_service.get_counts(&req).await?;
     Ok(Json(result))
 }
 
 #[post("/api/view")]
 async fn post_view_count(
-    Component(db): Component<DbConn>,
+    visitor: Visitor,
+    visit: PageVisit,
+    Component(pv_service): Component<PageViewService>,
     Json(req): Json<SetViewCount>,
 ) -> Result<impl IntoResponse> {
-    let count = PageViewCounter::increase_by_path(&db, &req)
-        .await
-        .context("increase view count failed")?;
+    let count = pv_service.set_count(&visitor, &visit, &req).await?;
 
     let count = match req.r#type {
-        ColumnQueryAs::Times => json!({"times":count.times}),
-        ColumnQueryAs::Reaction0 => json!({"reaction0":count.reaction0}),
-        ColumnQueryAs::Reaction1 => json!({"reaction1":count.reaction1}),
-        ColumnQueryAs::Reaction2 => json!({"reaction2":count.reaction2}),
-        ColumnQueryAs::Reaction3 => json!({"reaction3":count.reaction3}),
-        ColumnQueryAs::Reaction4 => json!({"reaction4":count.reaction4}),
-        ColumnQueryAs::Reaction5 => json!({"reaction5":count.reaction5}),
-        ColumnQueryAs::Reaction6 => json!({"reaction6":count.reaction6}),
-        ColumnQueryAs::Reaction7 => json!({"reaction7":count.reaction7}),
-        ColumnQueryAs::Reaction8 => json!({"reaction8":count.reaction8}),
+        CounterType::Times => json!({"times": count}),
+        ty => json!(HashMap::from([(ty, count)])),
     };
     Ok(Json(vec![count]))
 }
+
+#[get("/api/view/stats/trend")]
+async fn get_view_trend(
+    claims: OptionalClaims,
+    Component(pv_service): Component<PageViewService>,
+    Locale(lang): Locale,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    check_stats_query(&claims, &q, &lang)?;
+    let trend = pv_service.get_trend(&q).await?;
+    Ok(Json(json!({"data": trend})))
+}
+
+#[get("/api/view/stats/pages")]
+async fn get_top_pages(
+    claims: OptionalClaims,
+    Component(pv_service): Component<PageViewService>,
+    Locale(lang): Locale,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    check_stats_query(&claims, &q, &lang)?;
+    let pages = pv_service.get_top_pages(&q).await?;
+    Ok(Json(json!({"data": pages})))
+}
+
+/// dimension: referrer/country/browser/os
+#[get("/api/view/stats/top/:dimension")]
+async fn get_top_values(
+    claims: OptionalClaims,
+    Component(pv_service): Component<PageViewService>,
+    Path(dimension): Path<StatDimension>,
+    Locale(lang): Locale,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    check_stats_query(&claims, &q, &lang)?;
+    if dimension == StatDimension::Total {
+        Err(KnownWebError::bad_request("unsupported dimension"))?;
+    }
+    let top = pv_service.get_top(dimension, &q).await?;
+    Ok(Json(json!({"data": top})))
+}
+
+fn check_stats_query(claims: &OptionalClaims, q: &StatsQuery, lang: &str) -> Result<()> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+    if q.start > q.end {
+        Err(KnownWebError::bad_request("start must not be after end"))?;
+    }
+    Ok(())
+}
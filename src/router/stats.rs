@@ -0,0 +1,26 @@
+use crate::model::sea_orm_active_enums::UserType;
+use crate::router::Locale;
+use crate::service::stats::StatsService;
+use crate::utils::jwt::OptionalClaims;
+use crate::views::stats::StatsQuery;
+use rust_i18n::t;
+use spring_web::{
+    axum::{response::IntoResponse, Json},
+    error::{KnownWebError, Result},
+    extractor::{Component, Query},
+    get,
+};
+
+#[get("/api/stats")]
+async fn get_stats(
+    claims: OptionalClaims,
+    Component(stats_service): Component<StatsService>,
+    Locale(lang): Locale,
+    Query(q): Query<StatsQuery>,
+) -> Result<impl IntoResponse> {
+    if claims.as_ref().map(|c| &c.ty) != Some(&UserType::Admin) {
+        Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+    }
+    let stats = stats_service.get_stats(&q).await?;
+    Ok(Json(stats))
+}
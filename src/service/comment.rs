@@ -1,39 +1,62 @@
 use crate::config::comrak::ComrakConfig;
-use crate::config::RalineConfig;
-use crate::views::comment::{
-    AddCommentReq, AdminCommentQuery, AdminListResp, CommentResp, CommentUpdateReq,
-    CountCommentQuery, ListCommentQuery, ListResp, Owner, RecentCommentQuery,
-};
+use crate::config::path::PathConfig;
+use crate::config::reaction::ReactionConfig;
+use crate::config::sanitizer::SanitizerConfig;
+use crate::config::search::SearchConfig;
+use crate::config::{RalineConfig, RequiredMeta};
 use crate::model::sea_orm_active_enums::UserType;
-use crate::model::{page_view_counter, prelude::*, users};
+use crate::model::{
+    comment_reactions, comment_revisions, comment_votes, page_view_counter, prelude::*, users,
+};
 use crate::plugins::akismet::Akismet;
 use crate::plugins::uaparser::{ToStringExt, UAParser};
 use crate::utils::avatar::avatar_url;
+use crate::utils::cursor;
 use crate::utils::ip2region;
 use crate::utils::jwt::Claims;
+use crate::utils::markdown;
+use crate::utils::path::normalize;
+use crate::utils::visitor::Visitor;
+use crate::views::comment::{
+    AddCommentReq, AdminCommentQuery, AdminListResp, BatchAction, BatchCommentReq, CommentCursor,
+    CommentReactionQuery, CommentReactionsResp, CommentResp, CommentUpdateReq, CountCommentQuery,
+    ListCommentQuery, ListResp, Owner, PreviewCommentReq, RecentCommentQuery, SearchCommentQuery,
+    SearchHit, SearchRow,
+};
 use crate::{
-    model::{comments, sea_orm_active_enums::CommentStatus},
+    model::{
+        comments::{self, Mention, ModerationStage, ModerationVerdict},
+        sea_orm_active_enums::CommentStatus,
+    },
     utils::jwt::OptionalClaims,
 };
 use anyhow::Context;
-use comrak::markdown_to_html;
 use itertools::Itertools;
 use regex::Regex;
-use sea_orm::sqlx::types::chrono::Local;
+use rust_i18n::t;
+use sea_orm::sea_query::{
+    Alias, Expr, LikeExpr, OnConflict, OrderedStatement, Query, SelectStatement, SimpleExpr,
+    WindowStatement,
+};
+use sea_orm::sqlx::types::chrono::{Days, Local};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
-    QuerySelect, Set,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, Order, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, Statement, TransactionTrait,
 };
 use spring::config::ConfigRef;
 use spring::plugin::service::Service;
+use spring_redis::redis::{self, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use spring_redis::Redis;
 use spring_sea_orm::DbConn;
 use spring_web::error::KnownWebError;
 use spring_web::error::Result;
 use std::cmp::max;
+use std::collections::HashMap;
 use std::net::IpAddr;
-use std::ops::Deref;
+use std::sync::OnceLock;
 use std::time::Duration;
 use uaparser::{Client, Parser};
+use validator::{Validate, ValidationErrors};
 
 #[derive(Clone, Service)]
 pub struct CommentService {
@@ -43,8 +66,14 @@ pub struct CommentService {
     akismet: Akismet,
     #[component]
     uaparser: UAParser,
+    #[component]
+    redis: Redis,
     raline: ConfigRef<RalineConfig>,
     comrak: ConfigRef<ComrakConfig>,
+    reaction: ConfigRef<ReactionConfig>,
+    path: ConfigRef<PathConfig>,
+    search: ConfigRef<SearchConfig>,
+    sanitizer: ConfigRef<SanitizerConfig>,
 }
 
 impl CommentService {
@@ -52,12 +81,16 @@ impl CommentService {
         &self,
         q: &RecentCommentQuery,
         optional_claims: &OptionalClaims,
+        visitor: &Visitor,
     ) -> Result<Vec<CommentResp>> {
         let filter = comments::Column::Status.eq(CommentStatus::Approved);
         let filter = match &**optional_claims {
             None => filter,
             Some(c) => filter.or(comments::Column::UserId.eq(c.uid)),
         };
+        let filter = filter
+            .and(comments::Column::DeletedAt.is_null())
+            .and(comments::Column::PageId.not_in_subquery(hidden_pages()));
 
         let comments = Comments::find()
             .filter(filter)
@@ -66,6 +99,7 @@ impl CommentService {
             .all(&self.db)
             .await
             .context("find comments page failed")?;
+        let page_ids: HashMap<i32, i32> = comments.iter().map(|c| (c.id, c.page_id)).collect();
 
         let uids = comments.iter().filter_map(|c| c.user_id).collect_vec();
         let users = Users::find()
@@ -74,9 +108,12 @@ impl CommentService {
             .await
             .context("query users failed")?;
 
-        let comments = self
+        let mut comments = self
             .compute_comments(comments, &vec![], &users, optional_claims)
             .await;
+        self.fill_my_votes(&mut comments, visitor).await?;
+        self.fill_reactions(&mut comments, visitor).await?;
+        self.fill_pages(&mut comments, &page_ids).await?;
         Ok(comments)
     }
 
@@ -85,29 +122,15 @@ impl CommentService {
         q: &AdminCommentQuery,
         optional_claims: &OptionalClaims,
     ) -> Result<AdminListResp> {
-        let claims = match &**optional_claims {
-            None => return Err(KnownWebError::forbidden("没有权限"))?,
-            Some(claims) => match claims.ty == UserType::Admin {
-                true => claims,
-                false => Err(KnownWebError::forbidden("没有权限"))?,
-            },
-        };
-        let mut filter = comments::Column::Status.eq(q.status.clone());
-        filter = match q.owner {
-            Owner::All => filter,
-            Owner::Mine => {
-                let user_filter = match &claims.mail {
-                    Some(mail) => comments::Column::UserId
-                        .eq(claims.uid)
-                        .or(comments::Column::Mail.eq(mail)),
-                    None => comments::Column::UserId.eq(claims.uid),
-                };
-                filter.and(user_filter)
-            }
-        };
-        if let Some(keyword) = &q.keyword {
-            filter = filter.and(comments::Column::Content.like(format!("%{keyword}%")));
-        }
+        let claims = admin_claims(optional_claims)?;
+        let filter = admin_filter(
+            &q.status,
+            &q.owner,
+            &q.keyword,
+            q.trash,
+            claims,
+            &self.search,
+        );
 
         let total = Comments::find()
             .filter(filter.clone())
@@ -115,25 +138,33 @@ impl CommentService {
             .await
             .context("count comments failed")?;
 
-        let spam_count = Comments::find()
-            .filter(comments::Column::Status.eq(CommentStatus::Spam))
-            .count(&self.db)
+        let status_count = Comments::count_by_status(&self.db)
             .await
-            .context("count comments failed")?;
+            .context("count comments by status failed")?;
 
-        let waiting_count = Comments::find()
-            .filter(comments::Column::Status.eq(CommentStatus::Waiting))
-            .count(&self.db)
-            .await
-            .context("count comments failed")?;
-
-        let comments = Comments::find()
+        let select = Comments::find()
             .filter(filter)
             .order_by_desc(comments::Column::CreatedAt)
-            .paginate(&self.db, q.size)
-            .fetch_page(max(q.page - 1, 0))
-            .await
-            .context("find comments page failed")?;
+            .order_by_desc(comments::Column::Id);
+        let comments = match &q.cursor {
+            Some(cursor) => {
+                let cursor = decode_cursor(cursor)?;
+                select
+                    .filter(cursor.after(comments::Column::CreatedAt, &Order::Desc))
+                    .limit(q.size)
+                    .all(&self.db)
+                    .await
+            }
+            None => {
+                select
+                    .paginate(&self.db, q.size)
+                    .fetch_page(max(q.page - 1, 0))
+                    .await
+            }
+        }
+        .context("find comments page failed")?;
+        let next = next_cursor(&comments, q.size);
+        let page_ids: HashMap<i32, i32> = comments.iter().map(|c| (c.id, c.page_id)).collect();
 
         let uids = comments.iter().filter_map(|c| c.user_id).collect_vec();
         let users = Users::find()
@@ -142,15 +173,18 @@ impl CommentService {
             .await
             .context("query users failed")?;
 
+        let mut data = self
+            .compute_comments(comments, &vec![], &users, optional_claims)
+            .await;
+        self.fill_pages(&mut data, &page_ids).await?;
         Ok(AdminListResp {
             page: q.page,
-            total_pages: total / q.size,
+            total_pages: total.div_ceil(q.size),
             page_size: q.size,
-            spam_count,
-            waiting_count,
-            data: self
-                .compute_comments(comments, &vec![], &users, optional_claims)
-                .await,
+            spam_count: status_count.spam,
+            waiting_count: status_count.waiting,
+            data,
+            next,
         })
     }
 
@@ -158,14 +192,25 @@ impl CommentService {
         &self,
         q: &ListCommentQuery,
         claims: &OptionalClaims,
+        visitor: &Visitor,
+        lang: &str,
     ) -> Result<ListResp> {
-        let page = PageViewCounter::find_id_by_path(&self.db, &q.path)
+        let path = normalize(&q.path, &self.path);
+        let page = PageViewCounter::find_by_path(&self.db, &path)
             .await
             .context("find page failed")?;
         let page = match page {
             None => return Ok(ListResp::default()),
             Some(page) => page,
         };
+        let is_admin = claims.as_ref().map(|c| &c.ty) == Some(&UserType::Admin);
+        if page.hidden && !is_admin {
+            return Ok(ListResp {
+                locked: page.locked,
+                hidden: true,
+                ..Default::default()
+            });
+        }
         let filter = comments::Column::PageId.eq(page.id);
         let filter = match &**claims {
             None => filter.and(comments::Column::Status.eq(CommentStatus::Approved)),
@@ -181,32 +226,55 @@ impl CommentService {
                 }
             }
         };
+        // 已删除但仍有可见回复的评论需要以占位符的形式展示
+        let filter = with_replied_deleted(filter);
         let count = Comments::find()
-            .filter(filter.clone())
+            .filter(filter.clone().and(comments::Column::DeletedAt.is_null()))
             .count(&self.db)
             .await
             .context("count comments failed")?;
 
-        let ((column, order), offset) = q.sort_by.clone().into_column_order();
-
-        let root_comments = Comments::find()
+        // 分页只针对当前层级的评论，回复不计入总页数
+        let root_count = Comments::find()
             .filter(filter.clone().and(comments::Column::Rid.eq(q.rid)))
-            .order_by(comments::Column::Sticky, sea_orm::Order::Desc)
-            .order_by(column, order)
-            .paginate(&self.db, q.limit)
-            .fetch_page(offset)
+            .count(&self.db)
             .await
-            .context("find root comments failed")?;
+            .context("count root comments failed")?;
 
+        let ((column, order), offset) = q.sort_by.clone().into_column_order();
+        let select = Comments::find().filter(filter.clone().and(comments::Column::Rid.eq(q.rid)));
+        let select = if q.rid == 0 {
+            select
+                .order_by(comments::Column::Sticky, Order::Desc)
+                .order_by(column, order.clone())
+                .order_by(comments::Column::Id, order.clone())
+        } else {
+            // 回复按时间正序，和根评论下的children保持一致
+            select
+                .order_by_asc(comments::Column::CreatedAt)
+                .order_by_asc(comments::Column::Id)
+        };
+        let root_comments = match &q.cursor {
+            Some(cursor) => {
+                let cursor = decode_cursor(cursor)?;
+                let after = match q.rid {
+                    0 => cursor.after_sticky(column, &order),
+                    _ => cursor.after(comments::Column::CreatedAt, &Order::Asc),
+                };
+                select.filter(after).limit(q.limit).all(&self.db).await
+            }
+            None => select.paginate(&self.db, q.limit).fetch_page(offset).await,
+        }
+        .context("find root comments failed")?;
+        let next = next_cursor(&root_comments, q.limit);
+
+        let mut children_count = HashMap::new();
         let comments = if q.rid == 0 {
             // root comments to find children
             let rids = root_comments.iter().map(|c| c.id).collect_vec();
-
-            let children = Comments::find()
-                .filter(filter.and(comments::Column::Rid.is_in(rids)))
-                .all(&self.db)
-                .await
-                .context("find children comments failed")?;
+            let children_filter = filter.and(comments::Column::Rid.is_in(rids));
+            children_count = self.count_children(children_filter.clone()).await?;
+            let children = self.find_first_children(children_filter).await?;
 
             vec![children, root_comments.clone()].concat()
         } else {
@@ -219,12 +287,39 @@ impl CommentService {
             .await
             .context("query users failed")?;
 
+        let mut data = self
+            .compute_comments(root_comments, &comments, &users, claims)
+            .await;
+        if !is_admin {
+            mark_removed(&mut data, lang);
+        }
+        self.fill_my_votes(&mut data, visitor).await?;
+        self.fill_reactions(&mut data, visitor).await?;
+        if q.rid == 0 {
+            let limit = self.raline.reply_limit;
+            for c in data.iter_mut() {
+                let total = children_count
+                    .get(&c.object_id)
+                    .cloned()
+                    .unwrap_or_default();
+                c.children_count = Some(total);
+                if total > limit {
+                    let last = comments
+                        .iter()
+                        .filter(|cc| cc.rid == c.object_id)
+                        .max_by_key(|cc| (cc.created_at, cc.id));
+                    c.children_next = last.map(|cc| cursor::encode(&CommentCursor::from(cc)));
+                }
+            }
+        }
+
         Ok(ListResp {
             count,
-            total_pages: count / q.limit,
-            data: self
-                .compute_comments(root_comments, &comments, &users, claims)
-                .await,
+            total_pages: root_count.div_ceil(q.limit),
+            data,
+            locked: page.locked,
+            hidden: page.hidden,
+            next,
         })
     }
 
@@ -240,13 +335,21 @@ impl CommentService {
                 .or(comments::Column::UserId.eq(c.uid)),
         };
 
-        let path_id_map = PageViewCounter::find_ids_by_paths(&self.db, &q.url)
+        let urls = q.url.iter().map(|u| normalize(u, &self.path)).collect_vec();
+        let path_id_map = PageViewCounter::find_ids_by_paths(&self.db, &urls)
             .await
             .context("find pages failed")?;
 
         let path_ids = path_id_map.values().cloned().collect_vec();
 
-        let filter = filter.and(comments::Column::PageId.is_in(path_ids));
+        let mut filter = filter
+            .and(comments::Column::PageId.is_in(path_ids))
+            .and(comments::Column::DeletedAt.is_null());
+        // 隐藏的评论区对非管理员不返回评论数
+        let is_admin = claims.as_ref().map(|c| &c.ty) == Some(&UserType::Admin);
+        if !is_admin {
+            filter = filter.and(comments::Column::PageId.not_in_subquery(hidden_pages()));
+        }
         let count: Vec<(i32, i64)> = Comments::find()
             .select_only()
             .column_as(comments::Column::PageId, "page_id")
@@ -257,8 +360,7 @@ impl CommentService {
             .all(&self.db)
             .await
             .context("query comment count failed")?;
-        let count = q
-            .url
+        let count = urls
             .iter()
             .map(|u| {
                 let page_id = path_id_map.get(u);
@@ -277,46 +379,80 @@ impl CommentService {
         &self,
         claims: OptionalClaims,
         client_ip: IpAddr,
-        body: AddCommentReq,
+        mut body: AddCommentReq,
+        lang: &str,
     ) -> Result<CommentResp> {
-        let page = PageViewCounter::find_id_by_path(&self.db, &body.url)
+        body.normalize_fields();
+        self.validate_comment(&body, &claims, lang)?;
+        body.url = normalize(&body.url, &self.path);
+        let is_admin = claims.as_ref().map(|c| &c.ty) == Some(&UserType::Admin);
+        // 先检查锁定状态，避免被拒绝的评论也改写了页面标题
+        let existing = PageViewCounter::find_by_path(&self.db, &body.url)
             .await
             .context("find page failed")?;
-        let page_id = match page {
-            Some(page) => page.id,
-            None => {
-                page_view_counter::ActiveModel {
-                    path: Set(body.url.clone()),
-                    ..Default::default()
-                }
-                .insert(&self.db)
-                .await
-                .context("insert page failed")?
-                .id
+        if let Some(page) = existing {
+            if (page.locked || page.hidden) && !is_admin {
+                Err(KnownWebError::forbidden(t!("page_locked", locale = lang)))?;
             }
-        };
-        let mut data = body.clone().into_active_model(page_id);
+        }
+        let page = PageViewCounter::find_or_create_page(&self.db, &body.url, body.title.as_deref())
+            .await
+            .context("find page failed")?;
+        let mut data = body.clone().into_active_model(page.id);
         data.ip = Set(client_ip.to_string());
         data.user_id = Set(claims.as_ref().map(|c| c.uid));
-
-        if let Some(pid) = &body.pid {
-            match &body.at {
-                None => Err(KnownWebError::bad_request("at required with pid"))?,
-                Some(at) => {
-                    let comment = &body.comment;
-                    data.content = Set(format!("[@{at}](#{pid}): {comment}"))
-                }
-            }
-        }
+        let mention = if let Some(pid) = body.pid {
+            // 被回复的评论必须存在、已审核、未删除且在同一个页面，rid由服务端根据pid推导
+            let replied = Comments::find_by_id(pid)
+                .one(&self.db)
+                .await
+                .context("find parent comment failed")?
+                .filter(|c| c.page_id == page.id && c.deleted_at.is_none())
+                .filter(|c| is_admin || c.status == CommentStatus::Approved)
+                .ok_or_else(|| KnownWebError::bad_request("parent comment not exists"))?;
+            let mention = Mention {
+                pid,
+                nick: replied
+                    .nick
+                    .clone()
+                    .or_else(|| body.at.clone())
+                    .unwrap_or_default(),
+            };
+            data.mention = Set(Some(
+                serde_json::to_value(&mention).context("serialize mention failed")?,
+            ));
+            let parent = self.reply_parent(replied).await?;
+            data.pid = Set(parent.id);
+            data.rid = Set(if parent.rid == 0 {
+                parent.id
+            } else {
+                parent.rid
+            });
+            data.depth = Set(parent.depth + 1);
+            Some(mention)
+        } else {
+            None
+        };
+        data.comment_html = Set(Some(self.render(&body.comment, mention.as_ref(), is_admin)));
+        data.render_version = Set(self.render_version());
         tracing::debug!("Post Comment initial Data: {:?}", &body);
 
-        let mut status = CommentStatus::Approved;
-        if let Some(Claims { ty, .. }) = &*claims {
-            if *ty == UserType::Admin {
-                status = self.check_comment(&body, &client_ip).await?
+        // 管理员的评论直接通过，其他人的评论都要经过审核
+        let (status, verdict) = match &*claims {
+            Some(Claims {
+                ty: UserType::Admin,
+                ..
+            }) => (CommentStatus::Approved, None),
+            _ => {
+                let (status, verdict) = self.check_comment(&body, &client_ip).await?;
+                (status, Some(verdict))
             }
-        }
+        };
         data.status = Set(status);
+        data.moderation = Set(verdict
+            .map(serde_json::to_value)
+            .transpose()
+            .context("serialize moderation verdict failed")?);
 
         let c = data
             .insert(&self.db)
@@ -342,9 +478,16 @@ impl CommentService {
     pub async fn update_comment(
         &self,
         optional_claims: OptionalClaims,
+        visitor: &Visitor,
         id: i32,
         body: CommentUpdateReq,
+        lang: &str,
     ) -> Result<CommentResp> {
+        if let Some(like) = body.like {
+            let vote = if like { comment_votes::UP } else { 0 };
+            self.vote_comment(visitor, id, vote).await?;
+        }
+
         let c = Comments::find_by_id(id)
             .one(&self.db)
             .await
@@ -354,38 +497,74 @@ impl CommentService {
             None => Err(KnownWebError::not_found("not found"))?,
             Some(c) => c,
         };
-        let mut ac = comments::ActiveModel {
+        let ac = comments::ActiveModel {
             id: Set(c.id),
             ..Default::default()
         };
-        if let Some(like) = body.like {
-            match like {
-                true => ac.star = Set(max(c.star + 1, 0)),
-                false => ac.star = Set(max(c.star - 1, 0)),
-            }
-        }
 
         let c = match &*optional_claims {
             None => match body.is_empty() {
                 false => Err(KnownWebError::forbidden("forbidden"))?,
-                true => {
-                    let c = ac.update(&self.db).await.context("update comment failed")?;
-                    self.format_comment(&c, &vec![], &optional_claims).await
-                }
+                true => self.format_comment(&c, &vec![], &optional_claims).await,
             },
             Some(claims) => {
                 if body.is_empty() {
-                    let c = ac.update(&self.db).await.context("update comment failed")?;
                     self.format_comment(&c, &vec![], &optional_claims).await
                 } else {
                     if c.user_id != Some(claims.uid) && UserType::Admin != claims.ty {
                         Err(KnownWebError::forbidden("forbidden"))?;
                     }
-                    let c = body
-                        .update_active_model(ac, claims.ty.clone())
-                        .update(&self.db)
+                    if UserType::Admin != claims.ty {
+                        let page = PageViewCounter::find_by_id(c.page_id)
+                            .one(&self.db)
+                            .await
+                            .context("find page failed")?;
+                        if page.is_some_and(|p| p.locked || p.hidden) {
+                            Err(KnownWebError::forbidden(t!("page_locked", locale = lang)))?;
+                        }
+                    }
+                    let edited = body.comment.as_ref().is_some_and(|s| *s != c.content);
+                    if let Some(content) = body.comment.as_ref().filter(|_| edited) {
+                        self.check_length(content, lang)?;
+                    }
+                    if edited && claims.ty == UserType::Normal {
+                        if let Some(window) = self.raline.edit_window {
+                            let deadline = c.created_at + Duration::from_secs(window);
+                            if Local::now().naive_local() > deadline {
+                                Err(KnownWebError::forbidden(t!(
+                                    "edit_window_expired",
+                                    locale = lang
+                                )))?;
+                            }
+                        }
+                    }
+                    let html = match &body.comment {
+                        Some(content) if edited => {
+                            let admin = self.is_admin_user(c.user_id).await?;
+                            Some(self.render(content, c.mention().as_ref(), admin))
+                        }
+                        _ => None,
+                    };
+                    let mut ac = body.update_active_model(ac, claims.ty.clone());
+                    if let Some(html) = html {
+                        ac.comment_html = Set(Some(html));
+                        ac.render_version = Set(self.render_version());
+                    }
+                    let txn = self.db.begin().await.context("begin transaction failed")?;
+                    if edited {
+                        comment_revisions::ActiveModel {
+                            comment_id: Set(c.id),
+                            user_id: Set(Some(claims.uid)),
+                            content: Set(c.content.clone()),
+                            ..Default::default()
+                        }
+                        .insert(&txn)
                         .await
-                        .context("update comment failed")?;
+                        .context("insert comment revision failed")?;
+                        ac.edited_at = Set(Some(Local::now().naive_local()));
+                    }
+                    let c = ac.update(&txn).await.context("update comment failed")?;
+                    txn.commit().await.context("commit transaction failed")?;
                     let u = Users::find_by_id(claims.uid)
                         .one(&self.db)
                         .await
@@ -396,14 +575,867 @@ impl CommentService {
             }
         };
 
-        Ok(c)
+        let mut c = vec![c];
+        self.fill_my_votes(&mut c, visitor).await?;
+        self.fill_reactions(&mut c, visitor).await?;
+        Ok(c.remove(0))
+    }
+
+    pub async fn vote_comment(&self, visitor: &Visitor, id: i32, vote: i16) -> Result<()> {
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+
+        // 只能对已审核且未删除的评论投票，锁定或隐藏的评论区只读
+        Comments::find_by_id(id)
+            .filter(comments::Column::Status.eq(CommentStatus::Approved))
+            .filter(comments::Column::DeletedAt.is_null())
+            .filter(comments::Column::PageId.not_in_subquery(locked_or_hidden_pages()))
+            .one(&txn)
+            .await
+            .context("find comment failed")?
+            .ok_or_else(|| KnownWebError::not_found("not found"))?;
+
+        let voter = visitor.key();
+        let old = CommentVotes::find()
+            .filter(
+                comment_votes::Column::CommentId
+                    .eq(id)
+                    .and(comment_votes::Column::Voter.eq(&voter)),
+            )
+            .one(&txn)
+            .await
+            .context("find comment vote failed")?;
+        let old_vote = old.as_ref().map(|v| v.vote).unwrap_or_default();
+        if old_vote == vote {
+            return Ok(());
+        }
+
+        match old {
+            Some(v) if vote == 0 => {
+                CommentVotes::delete_by_id(v.id)
+                    .exec(&txn)
+                    .await
+                    .context("delete comment vote failed")?;
+            }
+            Some(v) => {
+                comment_votes::ActiveModel {
+                    id: Set(v.id),
+                    vote: Set(vote),
+                    ..Default::default()
+                }
+                .update(&txn)
+                .await
+                .context("update comment vote failed")?;
+            }
+            None => {
+                let v = comment_votes::ActiveModel {
+                    comment_id: Set(id),
+                    user_id: Set(visitor.user_id()),
+                    voter: Set(voter),
+                    vote: Set(vote),
+                    ..Default::default()
+                };
+                let inserted = CommentVotes::insert(v)
+                    .on_conflict(
+                        OnConflict::columns([
+                            comment_votes::Column::CommentId,
+                            comment_votes::Column::Voter,
+                        ])
+                        .do_nothing()
+                        .to_owned(),
+                    )
+                    .exec_without_returning(&txn)
+                    .await
+                    .context("insert comment vote failed")?;
+                // 并发的首次投票已经计过数了
+                if inserted == 0 {
+                    return Ok(());
+                }
+            }
+        }
+
+        let delta = |kind: i16| (vote == kind) as i32 - (old_vote == kind) as i32;
+        Comments::update_many()
+            .col_expr(
+                comments::Column::Star,
+                Expr::col(comments::Column::Star).add(delta(comment_votes::UP)),
+            )
+            .col_expr(
+                comments::Column::VoteDown,
+                Expr::col(comments::Column::VoteDown).add(delta(comment_votes::DOWN)),
+            )
+            .filter(comments::Column::Id.eq(id))
+            .exec(&txn)
+            .await
+            .context("update comment votes failed")?;
+
+        txn.commit().await.context("commit transaction failed")?;
+        Ok(())
+    }
+
+    pub async fn toggle_reaction(
+        &self,
+        visitor: &Visitor,
+        id: i32,
+        reaction: &str,
+    ) -> Result<CommentReactionsResp> {
+        if !self.reaction.comment.iter().any(|r| r == reaction) {
+            Err(KnownWebError::bad_request("unsupported reaction"))?;
+        }
+        // 只能对已审核且未删除的评论回应，锁定或隐藏的评论区只读
+        Comments::find_by_id(id)
+            .filter(comments::Column::Status.eq(CommentStatus::Approved))
+            .filter(comments::Column::DeletedAt.is_null())
+            .filter(comments::Column::PageId.not_in_subquery(locked_or_hidden_pages()))
+            .one(&self.db)
+            .await
+            .context("find comment failed")?
+            .ok_or_else(|| KnownWebError::not_found("not found"))?;
+
+        let voter = visitor.key();
+        let effect = CommentReactions::delete_many()
+            .filter(
+                comment_reactions::Column::CommentId
+                    .eq(id)
+                    .and(comment_reactions::Column::Reaction.eq(reaction))
+                    .and(comment_reactions::Column::Voter.eq(&voter)),
+            )
+            .exec(&self.db)
+            .await
+            .context("delete comment reaction failed")?;
+
+        if effect.rows_affected == 0 {
+            let reaction = comment_reactions::ActiveModel {
+                comment_id: Set(id),
+                user_id: Set(visitor.user_id()),
+                voter: Set(voter),
+                reaction: Set(reaction.to_string()),
+                ..Default::default()
+            };
+            CommentReactions::insert(reaction)
+                .on_conflict(
+                    OnConflict::columns([
+                        comment_reactions::Column::CommentId,
+                        comment_reactions::Column::Reaction,
+                        comment_reactions::Column::Voter,
+                    ])
+                    .do_nothing()
+                    .to_owned(),
+                )
+                .exec_without_returning(&self.db)
+                .await
+                .context("insert comment reaction failed")?;
+        }
+
+        let mut reactions = self
+            .find_reactions(comment_reactions::Column::CommentId.eq(id), visitor)
+            .await?;
+        Ok(reactions.remove(&id).unwrap_or_default())
+    }
+
+    /// 一次查询出整个页面所有评论的表情回应
+    pub async fn get_page_reactions(
+        &self,
+        q: &CommentReactionQuery,
+        visitor: &Visitor,
+    ) -> Result<HashMap<i32, CommentReactionsResp>> {
+        let path = normalize(&q.path, &self.path);
+        let page = PageViewCounter::find_by_path(&self.db, &path)
+            .await
+            .context("find page failed")?;
+        let page = match page {
+            Some(page) if !page.hidden => page,
+            _ => return Ok(HashMap::new()),
+        };
+        let page_comments = Query::select()
+            .column(comments::Column::Id)
+            .from(Comments)
+            .and_where(
+                comments::Column::PageId
+                    .eq(page.id)
+                    .and(comments::Column::Status.eq(CommentStatus::Approved))
+                    .and(comments::Column::DeletedAt.is_null()),
+            )
+            .to_owned();
+        self.find_reactions(
+            comment_reactions::Column::CommentId.in_subquery(page_comments),
+            visitor,
+        )
+        .await
+    }
+
+    async fn find_reactions(
+        &self,
+        filter: SimpleExpr,
+        visitor: &Visitor,
+    ) -> Result<HashMap<i32, CommentReactionsResp>> {
+        let counts: Vec<(i32, String, i64)> = CommentReactions::find()
+            .select_only()
+            .column(comment_reactions::Column::CommentId)
+            .column(comment_reactions::Column::Reaction)
+            .column_as(comment_reactions::Column::Id.count(), "count")
+            .filter(filter.clone())
+            .group_by(comment_reactions::Column::CommentId)
+            .group_by(comment_reactions::Column::Reaction)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count comment reactions failed")?;
+
+        let mine: Vec<(i32, String)> = CommentReactions::find()
+            .select_only()
+            .column(comment_reactions::Column::CommentId)
+            .column(comment_reactions::Column::Reaction)
+            .filter(filter.and(comment_reactions::Column::Voter.eq(visitor.key())))
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("find my comment reactions failed")?;
+
+        let mut reactions = HashMap::<i32, CommentReactionsResp>::new();
+        for (comment_id, reaction, count) in counts {
+            let r = reactions.entry(comment_id).or_default();
+            r.count.insert(reaction, count);
+        }
+        for (comment_id, reaction) in mine {
+            let r = reactions.entry(comment_id).or_default();
+            r.mine.push(reaction);
+        }
+        Ok(reactions)
+    }
+
+    async fn fill_reactions(&self, comments: &mut [CommentResp], visitor: &Visitor) -> Result<()> {
+        let ids = comment_ids(comments);
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut reactions = self
+            .find_reactions(comment_reactions::Column::CommentId.is_in(ids), visitor)
+            .await?;
+        for_each_comment(comments, &mut |c| {
+            c.reactions = reactions.remove(&c.object_id).unwrap_or_default();
+        });
+        Ok(())
+    }
+
+    /// 回复的父评论，超过max_depth时沿着pid向上找到允许的最深一层祖先
+    async fn reply_parent(&self, replied: comments::Model) -> Result<comments::Model> {
+        let mut parent = replied;
+        let Some(max_depth) = self.raline.max_depth else {
+            return Ok(parent);
+        };
+        let max_depth = max(max_depth, 1);
+        while parent.depth + 1 > max_depth && parent.pid != 0 {
+            parent = Comments::find_by_id(parent.pid)
+                .one(&self.db)
+                .await
+                .context("find parent comment failed")?
+                .ok_or_else(|| KnownWebError::bad_request("parent comment not exists"))?;
+        }
+        Ok(parent)
+    }
+
+    /// 每个根评论的回复数
+    async fn count_children(&self, filter: SimpleExpr) -> Result<HashMap<i32, u64>> {
+        let counts: Vec<(i32, i64)> = Comments::find()
+            .select_only()
+            .column(comments::Column::Rid)
+            .column_as(comments::Column::Id.count(), "count")
+            .filter(filter)
+            .group_by(comments::Column::Rid)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count children comments failed")?;
+        Ok(counts
+            .into_iter()
+            .map(|(rid, count)| (rid, count as u64))
+            .collect())
+    }
+
+    /// 用窗口函数查询每个根评论按时间正序的前reply_limit条回复
+    async fn find_first_children(&self, filter: SimpleExpr) -> Result<Vec<comments::Model>> {
+        let ranked = Query::select()
+            .column(comments::Column::Id)
+            .expr_window_as(
+                Expr::cust("row_number()"),
+                WindowStatement::partition_by(comments::Column::Rid)
+                    .order_by(comments::Column::CreatedAt, Order::Asc)
+                    .order_by(comments::Column::Id, Order::Asc)
+                    .to_owned(),
+                Alias::new("rn"),
+            )
+            .from(Comments)
+            .and_where(filter)
+            .to_owned();
+        let first_ids = Query::select()
+            .column(comments::Column::Id)
+            .from_subquery(ranked, Alias::new("ranked"))
+            .and_where(Expr::col(Alias::new("rn")).lte(self.raline.reply_limit))
+            .to_owned();
+        let children = Comments::find()
+            .filter(comments::Column::Id.in_subquery(first_ids))
+            .order_by_asc(comments::Column::CreatedAt)
+            .order_by_asc(comments::Column::Id)
+            .all(&self.db)
+            .await
+            .context("find children comments failed")?;
+        Ok(children)
+    }
+
+    /// 填充评论所在页面的路径和标题
+    async fn fill_pages(
+        &self,
+        comments: &mut [CommentResp],
+        page_ids: &HashMap<i32, i32>,
+    ) -> Result<()> {
+        let pages: HashMap<i32, page_view_counter::Model> = PageViewCounter::find()
+            .filter(page_view_counter::Column::Id.is_in(page_ids.values().cloned().unique()))
+            .all(&self.db)
+            .await
+            .context("query pages failed")?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        for_each_comment(comments, &mut |c| {
+            let page = page_ids.get(&c.object_id).and_then(|id| pages.get(id));
+            if let Some(page) = page {
+                c.url = Some(page.path.clone());
+                c.page_title = page.title.clone();
+            }
+        });
+        Ok(())
+    }
+
+    /// 全文检索评论，按相关度排序，关键字也会匹配页面路径
+    pub async fn search_comments(
+        &self,
+        q: &SearchCommentQuery,
+        optional_claims: &OptionalClaims,
+    ) -> Result<Vec<SearchHit>> {
+        let keyword = q.q.trim();
+        if keyword.is_empty() {
+            Err(KnownWebError::bad_request("search keyword required"))?;
+        }
+        let is_admin = optional_claims
+            .as_ref()
+            .is_some_and(|c| c.ty == UserType::Admin);
+        let ts_config = self.search.ts_config.as_str();
+
+        let mut filter = search_match(&self.search, keyword).or(comments::Column::PageId
+            .in_subquery(
+                Query::select()
+                    .column(page_view_counter::Column::Id)
+                    .from(PageViewCounter)
+                    .and_where(like_contains(page_view_counter::Column::Path, keyword))
+                    .to_owned(),
+            ));
+        // 只有管理员可以按邮箱搜索
+        if is_admin {
+            filter = filter.or(like_contains(comments::Column::Mail, keyword));
+        } else {
+            filter = filter
+                .and(comments::Column::Status.eq(CommentStatus::Approved))
+                .and(comments::Column::DeletedAt.is_null())
+                .and(comments::Column::PageId.not_in_subquery(hidden_pages()));
+        }
+        if let Some(path) = &q.path {
+            let page = PageViewCounter::find_id_by_path(&self.db, normalize(path, &self.path))
+                .await
+                .context("find page failed")?;
+            let Some(page) = page else {
+                return Ok(vec![]);
+            };
+            filter = filter.and(comments::Column::PageId.eq(page.id));
+        }
+
+        let rows: Vec<SearchRow> = Comments::find()
+            .select_only()
+            .columns([
+                comments::Column::Id,
+                comments::Column::PageId,
+                comments::Column::Nick,
+                comments::Column::Mail,
+                comments::Column::Status,
+                comments::Column::CreatedAt,
+            ])
+            .column_as(
+                Expr::cust_with_values(
+                    "ts_headline($1::regconfig, content, websearch_to_tsquery($1::regconfig, $2), \
+                    'StartSel=<mark>, StopSel=</mark>, MaxFragments=2')",
+                    [ts_config, keyword],
+                ),
+                "highlight",
+            )
+            .column_as(
+                Expr::cust_with_values(
+                    "ts_rank(search_vector, websearch_to_tsquery($1::regconfig, $2))",
+                    [ts_config, keyword],
+                ),
+                "rank",
+            )
+            .filter(filter)
+            .order_by_desc(Expr::cust("rank"))
+            .order_by_desc(comments::Column::CreatedAt)
+            .limit(q.limit.clamp(1, 100))
+            .offset(q.offset)
+            .into_model()
+            .all(&self.db)
+            .await
+            .context("search comments failed")?;
+
+        let pages: HashMap<i32, page_view_counter::Model> = PageViewCounter::find()
+            .filter(page_view_counter::Column::Id.is_in(rows.iter().map(|r| r.page_id).unique()))
+            .all(&self.db)
+            .await
+            .context("query pages failed")?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+        // 片段来自评论原文，只保留高亮用的mark标签
+        let mut sanitizer = ammonia::Builder::empty();
+        sanitizer.add_tags(["mark"]);
+        let hits = rows
+            .into_iter()
+            .map(|r| {
+                let page = pages.get(&r.page_id);
+                SearchHit {
+                    object_id: r.id,
+                    url: page.map(|p| p.path.clone()),
+                    page_title: page.and_then(|p| p.title.clone()),
+                    nick: r.nick,
+                    mail: r.mail.filter(|_| is_admin),
+                    status: Some(r.status).filter(|_| is_admin),
+                    highlight: sanitizer.clean(&r.highlight).to_string(),
+                    rank: r.rank,
+                    time: r.created_at.and_utc().timestamp_millis(),
+                }
+            })
+            .collect();
+        Ok(hits)
+    }
+
+    /// 填充当前访客对每条评论的投票状态
+    async fn fill_my_votes(&self, comments: &mut [CommentResp], visitor: &Visitor) -> Result<()> {
+        let ids = comment_ids(comments);
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let votes: HashMap<i32, i16> = CommentVotes::find()
+            .select_only()
+            .column(comment_votes::Column::CommentId)
+            .column(comment_votes::Column::Vote)
+            .filter(
+                comment_votes::Column::CommentId
+                    .is_in(ids)
+                    .and(comment_votes::Column::Voter.eq(visitor.key())),
+            )
+            .into_tuple::<(i32, i16)>()
+            .all(&self.db)
+            .await
+            .context("find comment votes failed")?
+            .into_iter()
+            .collect();
+        for_each_comment(comments, &mut |c| {
+            c.my_vote = votes.get(&c.object_id).cloned();
+        });
+        Ok(())
+    }
+
+    pub async fn get_comment_revisions(
+        &self,
+        claims: &Claims,
+        id: i32,
+        lang: &str,
+    ) -> Result<Vec<comment_revisions::Model>> {
+        let c = Comments::find_by_id(id)
+            .one(&self.db)
+            .await
+            .context("find comment failed")?
+            .ok_or_else(|| KnownWebError::not_found(t!("not_found", locale = lang)))?;
+
+        if c.user_id != Some(claims.uid) && claims.ty != UserType::Admin {
+            Err(KnownWebError::forbidden(t!("no_permission", locale = lang)))?;
+        }
+
+        let revisions = CommentRevisions::find()
+            .filter(comment_revisions::Column::CommentId.eq(id))
+            .order_by_desc(comment_revisions::Column::CreatedAt)
+            .all(&self.db)
+            .await
+            .context("find comment revisions failed")?;
+        Ok(revisions)
+    }
+
+    pub async fn batch_update_comments(
+        &self,
+        optional_claims: &OptionalClaims,
+        req: BatchCommentReq,
+    ) -> Result<u64> {
+        let claims = admin_claims(optional_claims)?;
+        let filter = match (req.ids, &req.filter) {
+            (Some(ids), _) => comments::Column::Id.is_in(ids),
+            (None, Some(f)) => admin_filter(
+                &f.status,
+                &f.owner,
+                &f.keyword,
+                f.trash,
+                claims,
+                &self.search,
+            ),
+            (None, None) => Err(KnownWebError::bad_request("ids or filter required"))?,
+        };
+
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+
+        let ids: Vec<i32> = Comments::find()
+            .select_only()
+            .column(comments::Column::Id)
+            .filter(filter)
+            .into_tuple()
+            .all(&txn)
+            .await
+            .context("find batch comments failed")?;
+
+        let update = match req.action {
+            BatchAction::Approve => Some(comments::ActiveModel {
+                status: Set(CommentStatus::Approved),
+                ..Default::default()
+            }),
+            BatchAction::Spam => Some(comments::ActiveModel {
+                status: Set(CommentStatus::Spam),
+                ..Default::default()
+            }),
+            BatchAction::Waiting => Some(comments::ActiveModel {
+                status: Set(CommentStatus::Waiting),
+                ..Default::default()
+            }),
+            BatchAction::Sticky => Some(comments::ActiveModel {
+                sticky: Set(true),
+                ..Default::default()
+            }),
+            BatchAction::Unsticky => Some(comments::ActiveModel {
+                sticky: Set(false),
+                ..Default::default()
+            }),
+            BatchAction::Delete => Some(comments::ActiveModel {
+                deleted_at: Set(Some(Local::now().naive_local())),
+                ..Default::default()
+            }),
+            BatchAction::Restore => Some(comments::ActiveModel {
+                deleted_at: Set(None),
+                ..Default::default()
+            }),
+            BatchAction::Purge => None,
+        };
+        let affected = match update {
+            None => Comments::purge(&txn, ids)
+                .await
+                .context("batch purge comments failed")?,
+            Some(mut ac) => {
+                ac.updated_at = Set(Local::now().naive_local());
+                Comments::update_many()
+                    .set(ac)
+                    .filter(comments::Column::Id.is_in(ids))
+                    .exec(&txn)
+                    .await
+                    .context("batch update comments failed")?
+                    .rows_affected
+            }
+        };
+
+        txn.commit().await.context("commit transaction failed")?;
+
+        tracing::info!(
+            "admin#{} batch {:?} {} comments",
+            claims.uid,
+            req.action,
+            affected
+        );
+        Ok(affected)
+    }
+
+    pub async fn purge_spam(&self, days: u64) -> Result<u64> {
+        let before = Local::now()
+            .naive_local()
+            .checked_sub_days(Days::new(days))
+            .ok_or_else(|| KnownWebError::bad_request("days out of range"))?;
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+        let ids: Vec<i32> = Comments::find()
+            .select_only()
+            .column(comments::Column::Id)
+            .filter(
+                comments::Column::Status
+                    .eq(CommentStatus::Spam)
+                    .and(comments::Column::CreatedAt.lt(before)),
+            )
+            .into_tuple()
+            .all(&txn)
+            .await
+            .context("find spam comments failed")?;
+        let purged = Comments::purge(&txn, ids)
+            .await
+            .context("purge spam comments failed")?;
+        txn.commit().await.context("commit transaction failed")?;
+        Ok(purged)
+    }
+
+    /// 重新渲染html缓存过期的评论，force时重新渲染所有评论，返回渲染的评论数
+    pub async fn rebuild_html(&self, force: bool) -> Result<u64> {
+        let version = self.render_version();
+        let admin_ids = self.admin_ids().await?;
+        let mut last_id = 0;
+        let mut rebuilt = 0;
+        loop {
+            let mut filter = comments::Column::Id.gt(last_id);
+            if !force {
+                filter = filter.and(
+                    comments::Column::RenderVersion
+                        .ne(version)
+                        .or(comments::Column::CommentHtml.is_null()),
+                );
+            }
+            let comments = Comments::find()
+                .filter(filter)
+                .order_by_asc(comments::Column::Id)
+                .limit(REBUILD_BATCH_SIZE)
+                .all(&self.db)
+                .await
+                .context("find comments failed")?;
+            let Some(last) = comments.last() else {
+                break;
+            };
+            last_id = last.id;
+            rebuilt += self.save_html(&comments, &admin_ids).await?;
+        }
+        Ok(rebuilt)
+    }
+
+    /// 重新渲染读取时发现html缓存过期的评论，由定时任务调用
+    pub async fn rebuild_stale_html(&self) -> Result<u64> {
+        let mut redis = self.redis.clone();
+        let admin_ids = self.admin_ids().await?;
+        let mut rebuilt = 0;
+        loop {
+            let ids: Vec<i32> = redis::cmd("SPOP")
+                .arg(STALE_HTML_KEY)
+                .arg(REBUILD_BATCH_SIZE)
+                .query_async(&mut redis)
+                .await
+                .with_context(|| format!("spop {STALE_HTML_KEY} failed"))?;
+            if ids.is_empty() {
+                break;
+            }
+            let comments = Comments::find()
+                .filter(comments::Column::Id.is_in(ids))
+                .all(&self.db)
+                .await
+                .context("find comments failed")?;
+            rebuilt += self.save_html(&comments, &admin_ids).await?;
+        }
+        Ok(rebuilt)
+    }
+
+    async fn admin_ids(&self) -> Result<Vec<i32>> {
+        let admin_ids = Users::find()
+            .select_only()
+            .column(users::Column::Id)
+            .filter(users::Column::Type.eq(UserType::Admin))
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("query admin users failed")?;
+        Ok(admin_ids)
+    }
+
+    /// 渲染一批评论，用一条update语句写回html缓存
+    async fn save_html(&self, comments: &[comments::Model], admin_ids: &[i32]) -> Result<u64> {
+        if comments.is_empty() {
+            return Ok(0);
+        }
+        let mut values: Vec<sea_orm::Value> = vec![self.render_version().into()];
+        let rows = comments
+            .iter()
+            .map(|c| {
+                let admin = c.user_id.is_some_and(|uid| admin_ids.contains(&uid));
+                let html = self.render(&c.content, c.mention().as_ref(), admin);
+                values.push(c.id.into());
+                values.push(html.into());
+                format!("(${}::int, ${}::text)", values.len() - 1, values.len())
+            })
+            .join(", ");
+        let sql = format!(
+            "update comments set comment_html = v.html, render_version = $1 \
+             from (values {rows}) as v(id, html) where comments.id = v.id"
+        );
+        let result = self
+            .db
+            .execute(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                sql,
+                values,
+            ))
+            .await
+            .context("update comments html failed")?;
+        Ok(result.rows_affected())
+    }
+
+    /// 校验字段格式、长度和必填项，错误信息按lang本地化
+    fn validate_comment(
+        &self,
+        body: &AddCommentReq,
+        claims: &OptionalClaims,
+        lang: &str,
+    ) -> Result<()> {
+        if let Err(errors) = body.validate() {
+            Err(KnownWebError::bad_request(validation_message(
+                &errors, lang,
+            )))?;
+        }
+        if body.url.trim().is_empty() {
+            Err(KnownWebError::bad_request(t!(
+                "field_required",
+                locale = lang,
+                field = "url"
+            )))?;
+        }
+        self.check_length(&body.comment, lang)?;
+        if claims.is_none() {
+            if self.raline.login_required {
+                Err(KnownWebError::unauthorized(t!(
+                    "login_required",
+                    locale = lang
+                )))?;
+            }
+            for meta in &self.raline.required_meta {
+                let (field, value) = match meta {
+                    RequiredMeta::Nick => ("nick", &body.nick),
+                    RequiredMeta::Mail => ("mail", &body.mail),
+                    RequiredMeta::Link => ("link", &body.link),
+                };
+                if value.is_none() {
+                    Err(KnownWebError::bad_request(t!(
+                        "field_required",
+                        locale = lang,
+                        field = field
+                    )))?;
+                }
+            }
+        }
+        if let Some(link) = &body.link {
+            let scheme = reqwest::Url::parse(link)
+                .map(|url| url.scheme().to_string())
+                .unwrap_or_default();
+            if !self.raline.link_schemes.contains(&scheme) {
+                Err(KnownWebError::bad_request(t!(
+                    "invalid_link",
+                    locale = lang
+                )))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_length(&self, content: &str, lang: &str) -> Result<()> {
+        let len = content.trim().chars().count();
+        if len < self.raline.comment_min_length {
+            Err(KnownWebError::bad_request(t!(
+                "comment_too_short",
+                locale = lang
+            )))?;
+        }
+        if len > self.raline.comment_max_length {
+            Err(KnownWebError::bad_request(t!(
+                "comment_too_long",
+                locale = lang
+            )))?;
+        }
+        Ok(())
+    }
+
+    /// 预览评论，返回的html和发表后保存的完全一致
+    pub async fn preview_comment(
+        &self,
+        claims: &OptionalClaims,
+        client_ip: IpAddr,
+        body: &PreviewCommentReq,
+        lang: &str,
+    ) -> Result<String> {
+        self.check_length(&body.comment, lang)?;
+        // 窗口开始时创建带过期时间的计数器，之后只自增，不会延长过期时间
+        let key = format!("preview:{client_ip}");
+        let window = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(60));
+        let mut redis = self.redis.clone();
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .set_options(&key, 0, window)
+            .ignore()
+            .incr(&key, 1)
+            .query_async(&mut redis)
+            .await
+            .with_context(|| format!("incr {key} failed"))?;
+        if count > self.raline.preview_limit {
+            Err(KnownWebError::too_many_requests(t!(
+                "preview_too_frequent",
+                locale = lang
+            )))?;
+        }
+
+        let is_admin = claims.as_ref().map(|c| &c.ty) == Some(&UserType::Admin);
+        let mention = match body.pid {
+            None => None,
+            Some(pid) => {
+                // 和评论列表一样，非管理员只能看到已审核、未删除且评论区未隐藏的评论
+                let mut select =
+                    Comments::find_by_id(pid).filter(comments::Column::DeletedAt.is_null());
+                if !is_admin {
+                    select = select
+                        .filter(comments::Column::Status.eq(CommentStatus::Approved))
+                        .filter(comments::Column::PageId.not_in_subquery(hidden_pages()));
+                }
+                let replied = select
+                    .one(&self.db)
+                    .await
+                    .context("find parent comment failed")?;
+                let nick = replied
+                    .and_then(|c| c.nick)
+                    .or_else(|| body.at.clone())
+                    .unwrap_or_default();
+                Some(Mention { pid, nick })
+            }
+        };
+        Ok(self.render(&body.comment, mention.as_ref(), is_admin))
+    }
+
+    /// 按评论作者选择sanitizer策略渲染评论
+    fn render(&self, content: &str, mention: Option<&Mention>, admin: bool) -> String {
+        let policy = self.sanitizer.policy(admin);
+        markdown::render(content, mention, &self.comrak, policy)
+    }
+
+    /// 配置在运行期间不会变化，只计算一次
+    fn render_version(&self) -> i64 {
+        static RENDER_VERSION: OnceLock<i64> = OnceLock::new();
+        *RENDER_VERSION.get_or_init(|| markdown::render_version(&self.comrak, &self.sanitizer))
+    }
+
+    async fn is_admin_user(&self, user_id: Option<i32>) -> Result<bool> {
+        let Some(uid) = user_id else {
+            return Ok(false);
+        };
+        let user = Users::find_by_id(uid)
+            .one(&self.db)
+            .await
+            .with_context(|| format!("find user#{uid} failed"))?;
+        Ok(user.is_some_and(|u| u.r#type == UserType::Admin))
     }
 
     async fn check_comment(
         &self,
         comment: &AddCommentReq,
         client_ip: &IpAddr,
-    ) -> Result<CommentStatus> {
+    ) -> Result<(CommentStatus, ModerationVerdict)> {
         if self.raline.disallow_ips.contains(&client_ip) {
             tracing::debug!("Comment IP {} is in disallowIPList", &client_ip);
             Err(KnownWebError::forbidden("禁止访问"))?;
@@ -450,10 +1482,16 @@ impl CommentService {
         }
         tracing::debug!("Comment post frequency check OK!");
 
-        let mut status = if self.raline.audit {
-            CommentStatus::Waiting
+        let (mut status, mut verdict) = if self.raline.audit {
+            (
+                CommentStatus::Waiting,
+                ModerationVerdict::new(ModerationStage::Audit),
+            )
         } else {
-            CommentStatus::Approved
+            (
+                CommentStatus::Approved,
+                ModerationVerdict::new(ModerationStage::Passed),
+            )
         };
         tracing::debug!("Comment initial status is {:?}", status);
 
@@ -464,6 +1502,7 @@ impl CommentService {
                 Ok(spam) => {
                     if spam {
                         status = CommentStatus::Spam;
+                        verdict = ModerationVerdict::new(ModerationStage::Akismet);
                     }
                 }
             }
@@ -475,13 +1514,22 @@ impl CommentService {
             let regex = format!("({})", self.raline.forbidden_words.iter().join("|"));
             let regex = Regex::new(&regex)
                 .with_context(|| format!("forbidden_words regex parse failed:{}", regex))?;
-            if regex.is_match(&comment.comment) {
+            let hits = regex
+                .find_iter(&comment.comment)
+                .map(|m| m.as_str())
+                .unique()
+                .collect_vec();
+            if !hits.is_empty() {
                 status = CommentStatus::Spam;
+                verdict = ModerationVerdict {
+                    stage: ModerationStage::Keyword,
+                    rule: Some(hits.join(",")),
+                };
             }
         }
         tracing::debug!("Comment keyword check result: {:?}", status);
 
-        Ok(status)
+        Ok((status, verdict))
     }
 
     async fn compute_comments(
@@ -536,17 +1584,46 @@ impl CommentService {
         } else {
             None
         };
-        let comrak_opts = self.comrak.deref().into();
-        let comment_html = markdown_to_html(&c.content, &comrak_opts);
-        let comment_html = ammonia::clean(&*comment_html);
-        let orig = if login_user.is_none() {
+        // 缓存的html过期时临时渲染，并记录下来由定时任务重新生成
+        let comment_html = match &c.comment_html {
+            Some(html) if c.render_version == self.render_version() => html.clone(),
+            _ => {
+                let mut redis = self.redis.clone();
+                let added: redis::RedisResult<()> = redis.sadd(STALE_HTML_KEY, c.id).await;
+                if let Err(e) = added {
+                    tracing::warn!("sadd {} failed:{}", STALE_HTML_KEY, e);
+                }
+                let admin = users
+                    .iter()
+                    .any(|u| c.user_id == Some(u.id) && u.r#type == UserType::Admin);
+                self.render(&c.content, c.mention().as_ref(), admin)
+            }
+        };
+        let moderation = if is_admin {
+            c.moderation_verdict()
+        } else {
+            None
+        };
+        // 已删除的评论只有管理员能看到原始内容
+        let removed = c.deleted_at.is_some() && !is_admin;
+        let comment_html = if removed { String::new() } else { comment_html };
+        let orig = if login_user.is_none() || removed {
             None
         } else {
             Some(c.content.to_owned())
         };
-        let user = users.iter().find(|u| c.user_id == Some(u.id));
-        let nick = user.map(|u| u.username.clone()).or(c.nick.to_owned());
-        let mail = user.and_then(|u| u.email.clone()).or(c.mail.to_owned());
+        let user = users
+            .iter()
+            .find(|u| c.user_id == Some(u.id))
+            .filter(|_| !removed);
+        let nick = user
+            .map(|u| u.username.clone())
+            .or(c.nick.to_owned())
+            .filter(|_| !removed);
+        let mail = user
+            .and_then(|u| u.email.clone())
+            .or(c.mail.to_owned())
+            .filter(|_| !removed);
         let avatar = user.and_then(|u| u.avatar.clone());
         let avatar = match avatar {
             Some(avatar) => avatar,
@@ -560,7 +1637,7 @@ impl CommentService {
             status: c.status.to_owned(),
             comment: comment_html,
             inserted_at: c.created_at,
-            link: c.link.to_owned(),
+            link: c.link.to_owned().filter(|_| !removed),
             nick: nick,
             mail: mail,
             r#type: user.map(|u| u.r#type.clone()),
@@ -570,8 +1647,11 @@ impl CommentService {
             user_id: c.user_id,
             sticky: c.sticky,
             like: c.star,
+            dislike: c.vote_down,
+            my_vote: None,
+            reactions: Default::default(),
             object_id: c.id,
-            level: 0,
+            level: c.depth,
             browser: client
                 .clone()
                 .map(|c| c.user_agent.to_string())
@@ -579,8 +1659,175 @@ impl CommentService {
             os: client.map(|c| c.os.to_string()).unwrap_or_default(),
             orig,
             addr,
+            moderation,
+            deleted: c.deleted_at.map(|_| true),
+            edited: c.edited_at.map(|_| true),
+            edited_at: c.edited_at.map(|t| t.and_utc().timestamp_millis()),
+            url: None,
+            page_title: None,
             time: c.created_at.and_utc().timestamp_millis(),
             children: Default::default(),
+            children_count: None,
+            children_next: None,
+        }
+    }
+}
+
+/// 取第一个校验错误，message为locales中的key
+fn validation_message(errors: &ValidationErrors, lang: &str) -> String {
+    let error = errors
+        .field_errors()
+        .into_iter()
+        .sorted_by_key(|(field, _)| *field)
+        .find_map(|(field, errors)| errors.first().map(|e| (field, e)));
+    match error {
+        Some((field, error)) => {
+            let key = error.message.as_deref().unwrap_or(&error.code);
+            t!(key, locale = lang, field = field).to_string()
         }
+        None => errors.to_string(),
+    }
+}
+
+/// rebuild_html每批处理的评论数
+const REBUILD_BATCH_SIZE: u64 = 500;
+/// 读取时发现html缓存过期的评论id
+const STALE_HTML_KEY: &str = "comment:stale_html";
+
+fn admin_claims(optional_claims: &OptionalClaims) -> Result<&Claims> {
+    match &**optional_claims {
+        Some(claims) if claims.ty == UserType::Admin => Ok(claims),
+        _ => Err(KnownWebError::forbidden("没有权限"))?,
+    }
+}
+
+fn admin_filter(
+    status: &CommentStatus,
+    owner: &Owner,
+    keyword: &Option<String>,
+    trash: bool,
+    claims: &Claims,
+    search: &SearchConfig,
+) -> SimpleExpr {
+    let mut filter = comments::Column::Status.eq(status.clone());
+    filter = match trash {
+        true => filter.and(comments::Column::DeletedAt.is_not_null()),
+        false => filter.and(comments::Column::DeletedAt.is_null()),
+    };
+    filter = match owner {
+        Owner::All => filter,
+        Owner::Mine => {
+            let user_filter = match &claims.mail {
+                Some(mail) => comments::Column::UserId
+                    .eq(claims.uid)
+                    .or(comments::Column::Mail.eq(mail)),
+                None => comments::Column::UserId.eq(claims.uid),
+            };
+            filter.and(user_filter)
+        }
+    };
+    if let Some(keyword) = keyword {
+        filter = filter
+            .and(search_match(search, keyword).or(like_contains(comments::Column::Mail, keyword)));
+    }
+    filter
+}
+
+/// 使用search_vector全文检索，支持websearch语法：`"短语"`、`or`、`-排除`，
+/// 开启like_fallback时同时用like匹配评论内容
+fn search_match(search: &SearchConfig, keyword: &str) -> SimpleExpr {
+    let filter = Expr::cust_with_values(
+        "search_vector @@ websearch_to_tsquery($1::regconfig, $2)",
+        [search.ts_config.as_str(), keyword],
+    );
+    match search.like_fallback {
+        true => filter.or(like_contains(comments::Column::Content, keyword)),
+        false => filter,
+    }
+}
+
+/// like模糊匹配，转义关键字中的通配符
+fn like_contains<C: ColumnTrait>(col: C, keyword: &str) -> SimpleExpr {
+    let keyword = keyword
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    col.like(LikeExpr::new(format!("%{keyword}%")).escape('\\'))
+}
+
+/// 隐藏了评论区的页面id
+fn hidden_pages() -> SelectStatement {
+    Query::select()
+        .column(page_view_counter::Column::Id)
+        .from(PageViewCounter)
+        .and_where(page_view_counter::Column::Hidden.eq(true))
+        .to_owned()
+}
+
+/// 锁定或隐藏了评论区的页面id
+fn locked_or_hidden_pages() -> SelectStatement {
+    Query::select()
+        .column(page_view_counter::Column::Id)
+        .from(PageViewCounter)
+        .and_where(
+            page_view_counter::Column::Locked
+                .eq(true)
+                .or(page_view_counter::Column::Hidden.eq(true)),
+        )
+        .to_owned()
+}
+
+/// 未删除的评论，或者已删除但仍有可见回复的评论
+fn with_replied_deleted(filter: SimpleExpr) -> SimpleExpr {
+    let visible = filter.clone().and(comments::Column::DeletedAt.is_null());
+    let replied = |col: comments::Column| {
+        Query::select()
+            .column(col)
+            .from(Comments)
+            .and_where(visible.clone())
+            .to_owned()
+    };
+    filter.and(
+        comments::Column::DeletedAt
+            .is_null()
+            .or(comments::Column::Id.in_subquery(replied(comments::Column::Pid)))
+            .or(comments::Column::Id.in_subquery(replied(comments::Column::Rid))),
+    )
+}
+
+fn mark_removed(comments: &mut [CommentResp], lang: &str) {
+    for c in comments.iter_mut() {
+        if c.deleted == Some(true) {
+            c.comment = t!("comment_removed", locale = lang).to_string();
+        }
+        mark_removed(&mut c.children, lang);
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<CommentCursor> {
+    Ok(cursor::decode(cursor).ok_or_else(|| KnownWebError::bad_request("invalid cursor"))?)
+}
+
+/// 取满一页时用最后一条评论作为下一页的游标
+fn next_cursor(comments: &[comments::Model], limit: u64) -> Option<String> {
+    if (comments.len() as u64) < limit {
+        return None;
+    }
+    comments
+        .last()
+        .map(|c| cursor::encode(&CommentCursor::from(c)))
+}
+
+fn comment_ids(comments: &[CommentResp]) -> Vec<i32> {
+    comments
+        .iter()
+        .flat_map(|c| [vec![c.object_id], comment_ids(&c.children)].concat())
+        .collect()
+}
+
+fn for_each_comment(comments: &mut [CommentResp], f: &mut impl FnMut(&mut CommentResp)) {
+    for c in comments.iter_mut() {
+        f(c);
+        for_each_comment(&mut c.children, f);
     }
 }
@@ -1,2 +1,5 @@
+pub mod auth;
 pub mod comment;
-pub mod auth;
\ No newline at end of file
+pub mod page;
+pub mod pv_counter;
+pub mod stats;
@@ -0,0 +1,291 @@
+use crate::config::path::PathConfig;
+use crate::model::{page_aliases, page_view_counter, prelude::*};
+use crate::service::pv_counter::{move_buffered, uv_key};
+use crate::utils::path::normalize;
+use crate::views::page::{MergePageReq, PageAliasReq, PageMetaReq, RenamePageReq};
+use anyhow::Context;
+use itertools::Itertools;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, IntoActiveModel,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use spring::config::ConfigRef;
+use spring::plugin::service::Service;
+use spring_redis::redis::AsyncCommands;
+use spring_redis::Redis;
+use spring_sea_orm::DbConn;
+use spring_web::error::{KnownWebError, Result};
+
+#[derive(Clone, Service)]
+pub struct PageService {
+    #[component]
+    db: DbConn,
+    #[component]
+    redis: Redis,
+    path: ConfigRef<PathConfig>,
+}
+
+impl PageService {
+    /// 按照当前的规范化规则合并路径重复的页面，返回被合并掉的页面数
+    pub async fn normalize_pages(&self) -> Result<usize> {
+        let pages = PageViewCounter::find()
+            .order_by_asc(page_view_counter::Column::Id)
+            .all(&self.db)
+            .await
+            .context("query pages failed")?;
+        let groups = pages
+            .into_iter()
+            .into_group_map_by(|p| normalize(&p.path, &self.path));
+
+        let mut merged = 0;
+        for (path, pages) in groups {
+            // 优先保留路径已经规范的页面，否则保留最早的页面并改名
+            let target = pages
+                .iter()
+                .find(|p| p.path == path)
+                .unwrap_or(&pages[0])
+                .clone();
+            let sources = pages.iter().filter(|p| p.id != target.id).collect_vec();
+            if sources.is_empty() && target.path == path {
+                continue;
+            }
+
+            let txn = self.db.begin().await.context("begin transaction failed")?;
+            let source_ids = sources.iter().map(|p| p.id).collect_vec();
+            // 锁住被合并的页面再读取浏览量，避免丢失读取之后新增的浏览量
+            let source_times: i32 = PageViewCounter::find()
+                .filter(page_view_counter::Column::Id.is_in(source_ids.clone()))
+                .lock_exclusive()
+                .all(&txn)
+                .await
+                .context("lock pages failed")?
+                .iter()
+                .map(|p| p.times)
+                .sum();
+            PageViewCounter::merge_pages(&txn, target.id, &source_ids)
+                .await
+                .with_context(|| format!("merge pages into {path} failed"))?;
+            PageViewCounter::update_many()
+                .col_expr(page_view_counter::Column::Path, Expr::value(path.clone()))
+                .col_expr(
+                    page_view_counter::Column::Times,
+                    Expr::col(page_view_counter::Column::Times).add(source_times),
+                )
+                .filter(page_view_counter::Column::Id.eq(target.id))
+                .exec(&txn)
+                .await
+                .with_context(|| format!("update page {path} failed"))?;
+            // 旧路径保留为别名，还在使用旧路径的请求仍然能找到页面
+            for old in pages.iter().filter(|p| p.path != path) {
+                save_alias(&txn, &old.path, target.id)
+                    .await
+                    .context("save page alias failed")?;
+            }
+            txn.commit().await.context("commit transaction failed")?;
+
+            let old_paths = pages.iter().map(|p| p.path.as_str()).collect_vec();
+            for old in &old_paths {
+                move_buffered(&self.redis, old, &path).await?;
+            }
+            self.merge_unique_visitors(&path, &old_paths).await?;
+            merged += sources.len();
+        }
+        Ok(merged)
+    }
+
+    /// 修改页面路径，评论和计数都跟着页面走
+    pub async fn rename_page(&self, req: &RenamePageReq) -> Result<page_view_counter::Model> {
+        let from = normalize(&req.from, &self.path);
+        let to = normalize(&req.to, &self.path);
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+        let page = PageViewCounter::find()
+            .filter(page_view_counter::Column::Path.eq(&from))
+            .one(&txn)
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        let exists = PageViewCounter::find()
+            .filter(page_view_counter::Column::Path.eq(&to))
+            .one(&txn)
+            .await
+            .context("find page failed")?;
+        if exists.is_some() {
+            Err(KnownWebError::bad_request(
+                "target page already exists, merge it instead",
+            ))?;
+        }
+
+        // 新路径之前可能是这个页面的别名，是其他页面的别名时不能占用
+        let alias = PageAliases::find_by_id(to.clone())
+            .one(&txn)
+            .await
+            .context("find page alias failed")?;
+        if let Some(alias) = alias {
+            if alias.page_id != page.id {
+                Err(KnownWebError::conflict(
+                    "target path is an alias of another page",
+                ))?;
+            }
+            PageAliases::delete_by_id(to.clone())
+                .exec(&txn)
+                .await
+                .context("delete page alias failed")?;
+        }
+        let mut page = page.into_active_model();
+        page.path = Set(to);
+        let page = page.update(&txn).await.context("rename page failed")?;
+        if req.keep_alias {
+            save_alias(&txn, &from, page.id)
+                .await
+                .context("save page alias failed")?;
+        }
+        txn.commit().await.context("commit transaction failed")?;
+
+        move_buffered(&self.redis, &from, &page.path).await?;
+        self.merge_unique_visitors(&page.path, &[&from]).await?;
+        Ok(page)
+    }
+
+    /// 把from页面合并到to页面，返回合并后的页面
+    pub async fn merge_page(&self, req: &MergePageReq) -> Result<page_view_counter::Model> {
+        let from = normalize(&req.from, &self.path);
+        let to = normalize(&req.to, &self.path);
+        if from == to {
+            Err(KnownWebError::bad_request("can't merge a page into itself"))?;
+        }
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+        let source = PageViewCounter::find()
+            .filter(page_view_counter::Column::Path.eq(&from))
+            .one(&txn)
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        let target = PageViewCounter::find()
+            .filter(page_view_counter::Column::Path.eq(&to))
+            .one(&txn)
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+
+        PageViewCounter::merge_pages(&txn, target.id, &[source.id])
+            .await
+            .context("merge pages failed")?;
+        let page = PageViewCounter::update_many()
+            .col_expr(
+                page_view_counter::Column::Times,
+                Expr::col(page_view_counter::Column::Times).add(source.times),
+            )
+            .filter(page_view_counter::Column::Id.eq(target.id))
+            .exec_with_returning(&txn)
+            .await
+            .context("update page failed")?
+            .pop()
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        save_alias(&txn, &from, target.id)
+            .await
+            .context("save page alias failed")?;
+        txn.commit().await.context("commit transaction failed")?;
+
+        move_buffered(&self.redis, &from, &to).await?;
+        self.merge_unique_visitors(&to, &[&from]).await?;
+        Ok(page)
+    }
+
+    /// 修改页面标题，锁定或者隐藏评论区
+    pub async fn update_meta(&self, req: &PageMetaReq) -> Result<page_view_counter::Model> {
+        let page = PageViewCounter::find_by_path(&self.db, &normalize(&req.path, &self.path))
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        let mut page = page.into_active_model();
+        if let Some(title) = &req.title {
+            let title: String = title.trim().chars().take(255).collect();
+            page.title = Set(Some(title).filter(|t| !t.is_empty()));
+        }
+        if let Some(locked) = req.locked {
+            page.locked = Set(locked);
+        }
+        if let Some(hidden) = req.hidden {
+            page.hidden = Set(hidden);
+        }
+        let page = page.update(&self.db).await.context("update page failed")?;
+        Ok(page)
+    }
+
+    /// 手动注册别名，访问path时等同于访问target页面
+    pub async fn add_alias(&self, req: &PageAliasReq) -> Result<()> {
+        let path = normalize(&req.path, &self.path);
+        let target = normalize(&req.target, &self.path);
+        let exists = PageViewCounter::find()
+            .filter(page_view_counter::Column::Path.eq(&path))
+            .one(&self.db)
+            .await
+            .context("find page failed")?;
+        if exists.is_some() {
+            Err(KnownWebError::bad_request(
+                "page already exists, merge it instead",
+            ))?;
+        }
+        let page = PageViewCounter::find_id_by_path(&self.db, target)
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        save_alias(&self.db, &path, page.id)
+            .await
+            .context("save page alias failed")?;
+        Ok(())
+    }
+
+    pub async fn remove_alias(&self, path: &str) -> Result<u64> {
+        let path = normalize(path, &self.path);
+        let result = PageAliases::delete_by_id(path)
+            .exec(&self.db)
+            .await
+            .context("delete page alias failed")?;
+        Ok(result.rows_affected)
+    }
+
+    pub async fn get_aliases(&self, path: &str) -> Result<Vec<page_aliases::Model>> {
+        let page = PageViewCounter::find_id_by_path(&self.db, normalize(path, &self.path))
+            .await
+            .context("find page failed")?
+            .ok_or_else(|| KnownWebError::not_found("page not exists"))?;
+        let aliases = PageAliases::find()
+            .filter(page_aliases::Column::PageId.eq(page.id))
+            .all(&self.db)
+            .await
+            .context("query page aliases failed")?;
+        Ok(aliases)
+    }
+
+    async fn merge_unique_visitors(&self, target: &str, sources: &[&str]) -> Result<()> {
+        let mut keys = sources.iter().map(|p| uv_key(p)).collect_vec();
+        keys.push(uv_key(target));
+        let mut redis = self.redis.clone();
+        let _: () = redis
+            .pfmerge(uv_key(target), keys)
+            .await
+            .with_context(|| format!("merge unique visitors of {target} failed"))?;
+        Ok(())
+    }
+}
+
+async fn save_alias<C>(db: &C, path: &str, page_id: i32) -> std::result::Result<(), DbErr>
+where
+    C: ConnectionTrait,
+{
+    PageAliases::insert(page_aliases::ActiveModel {
+        path: Set(path.to_string()),
+        page_id: Set(page_id),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(page_aliases::Column::Path)
+            .update_column(page_aliases::Column::PageId)
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,755 @@
+use crate::config::pageview::PageViewConfig;
+use crate::config::path::PathConfig;
+use crate::config::reaction::ReactionConfig;
+use crate::model::page_view_stats::{self, StatDimension};
+use crate::model::{page_reaction_voters, page_reactions, page_view_counter, prelude::*};
+use crate::plugins::uaparser::UAParser;
+use crate::utils::ip2region;
+use crate::utils::path::normalize;
+use crate::utils::rand;
+use crate::utils::visitor::{PageVisit, Visitor};
+use crate::views::pv_counter::{
+    CounterType, SetCountAction, SetViewCount, StatsQuery, TopItem, TrendItem, ViewCountQuery,
+};
+use anyhow::Context;
+use itertools::Itertools;
+use sea_orm::prelude::Date;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::sqlx::types::chrono::Local;
+use sea_orm::{
+    ColumnTrait, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use spring::config::ConfigRef;
+use spring::plugin::service::Service;
+use spring_redis::redis::{self, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use spring_redis::Redis;
+use spring_sea_orm::DbConn;
+use spring_web::error::{KnownWebError, Result};
+use std::collections::HashMap;
+use uaparser::Parser;
+
+/// 待写入数据库的浏览量增量：path -> delta
+const PV_DELTA_KEY: &str = "pv:delta";
+/// 正在写入数据库的浏览量增量
+const PV_FLUSHING_KEY: &str = "pv:delta:flushing";
+/// 待写入数据库的按天统计增量：day\tdimension\tvalue\tpath -> delta
+const PV_STATS_KEY: &str = "pv:stats";
+/// 正在写入数据库的按天统计增量
+const PV_STATS_FLUSHING_KEY: &str = "pv:stats:flushing";
+
+/// 多实例同时执行定时任务时，同一时间只允许一个实例写入
+const FLUSH_LOCK_SECONDS: u64 = 600;
+/// 缓冲模式下页面路径和已写入数据库的浏览量的缓存时间
+const PAGE_CACHE_SECONDS: u64 = 300;
+
+/// 每个页面独立访客的HyperLogLog
+pub(crate) fn uv_key(path: &str) -> String {
+    format!("pv:uv:{path}")
+}
+
+/// 页面改名或合并后，把redis中还未写入数据库的增量转移到新路径，
+/// 否则写入时会按旧路径重新创建页面
+pub(crate) async fn move_buffered(redis: &Redis, from: &str, to: &str) -> Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    let mut redis = redis.clone();
+    // 页面路径和浏览量都变了，缓存需要重新从数据库读取
+    let _: () = redis
+        .del(&[
+            canonical_key(from),
+            canonical_key(to),
+            base_times_key(from),
+            base_times_key(to),
+        ])
+        .await
+        .with_context(|| format!("del page cache of {from} failed"))?;
+    let script = redis::Script::new(MOVE_FIELD_SCRIPT);
+    for key in [PV_DELTA_KEY, PV_FLUSHING_KEY] {
+        let _: Option<i64> = script
+            .key(key)
+            .arg(from)
+            .arg(to)
+            .invoke_async(&mut redis)
+            .await
+            .with_context(|| format!("move {key} {from} failed"))?;
+    }
+    for key in [PV_STATS_KEY, PV_STATS_FLUSHING_KEY] {
+        let fields: Vec<String> = redis
+            .hkeys(key)
+            .await
+            .with_context(|| format!("hkeys {key} failed"))?;
+        for field in fields {
+            let Some((day, dimension, value, path)) = parse_stats_field(&field) else {
+                continue;
+            };
+            if path != from {
+                continue;
+            }
+            let moved = format!("{day}\t{}\t{value}\t{to}", dimension.as_ref());
+            let _: Option<i64> = script
+                .key(key)
+                .arg(&field)
+                .arg(moved)
+                .invoke_async(&mut redis)
+                .await
+                .with_context(|| format!("move {key} {from} failed"))?;
+        }
+    }
+    Ok(())
+}
+
+/// 原子地把hash中的一个field累加到另一个field上并删除
+const MOVE_FIELD_SCRIPT: &str = r"
+local delta = redis.call('HGET', KEYS[1], ARGV[1])
+if delta then
+    redis.call('HINCRBY', KEYS[1], ARGV[2], delta)
+    redis.call('HDEL', KEYS[1], ARGV[1])
+end
+return delta
+";
+
+/// 请求路径对应的页面当前路径，别名会解析为页面路径
+fn canonical_key(path: &str) -> String {
+    format!("pv:canonical:{path}")
+}
+
+/// 页面已写入数据库的浏览量
+fn base_times_key(path: &str) -> String {
+    format!("pv:times:{path}")
+}
+
+/// 只有持有锁的实例才能释放锁，避免锁过期后释放了其他实例的锁
+const UNLOCK_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// 访客最近访问过的页面，用于去重
+fn seen_key(path: &str, visitor: &Visitor) -> String {
+    format!("pv:seen:{path}:{}", visitor.key())
+}
+
+#[derive(Clone, Service)]
+pub struct PageViewService {
+    #[component]
+    db: DbConn,
+    #[component]
+    redis: Redis,
+    #[component]
+    uaparser: UAParser,
+    reaction: ConfigRef<ReactionConfig>,
+    pageview: ConfigRef<PageViewConfig>,
+    path: ConfigRef<PathConfig>,
+}
+
+impl PageViewService {
+    pub async fn get_counts(&self, q: &ViewCountQuery) -> Result<Vec<HashMap<CounterType, i32>>> {
+        let paths = q
+            .path
+            .iter()
+            .map(|p| normalize(p, &self.path))
+            .collect_vec();
+        if paths.is_empty() {
+            let result = q.types.iter().map(|ty| (*ty, 0)).collect();
+            return Ok(vec![result]);
+        }
+        // 旧路径通过别名对应到新页面
+        let path_ids = PageViewCounter::find_ids_by_paths(&self.db, &paths)
+            .await
+            .context("find pages failed")?;
+        let pages = PageViewCounter::find()
+            .filter(page_view_counter::Column::Id.is_in(path_ids.values().cloned()))
+            .all(&self.db)
+            .await
+            .context("query view counter failed")?;
+        let page_ids = pages.iter().map(|p| p.id).collect_vec();
+
+        let keys = q
+            .types
+            .iter()
+            .filter_map(|ty| self.reaction_key(ty))
+            .collect_vec();
+        let reactions: Vec<(i32, String, i32)> = if keys.is_empty() {
+            vec![]
+        } else {
+            PageReactions::find()
+                .select_only()
+                .column(page_reactions::Column::PageId)
+                .column(page_reactions::Column::ReactionKey)
+                .column(page_reactions::Column::Count)
+                .filter(
+                    page_reactions::Column::PageId
+                        .is_in(page_ids)
+                        .and(page_reactions::Column::ReactionKey.is_in(keys)),
+                )
+                .into_tuple()
+                .all(&self.db)
+                .await
+                .context("query page reactions failed")?
+        };
+
+        let page_map: HashMap<i32, &page_view_counter::Model> =
+            pages.iter().map(|p| (p.id, p)).collect();
+        // redis中的增量和独立访客按页面当前路径记录
+        let canonical = paths
+            .iter()
+            .map(
+                |path| match path_ids.get(path).and_then(|id| page_map.get(id)) {
+                    Some(page) => page.path.clone(),
+                    None => path.clone(),
+                },
+            )
+            .collect_vec();
+        let buffered = self.buffered_times(&canonical).await?;
+        let unique = match q.types.contains(&CounterType::Unique) {
+            true => self.unique_visitors(&canonical).await?,
+            false => HashMap::new(),
+        };
+
+        let mut result = vec![];
+        for (path, canonical) in paths.iter().zip(&canonical) {
+            let page = path_ids.get(path).and_then(|id| page_map.get(id));
+            let mut counter = HashMap::<CounterType, i32>::with_capacity(q.types.len());
+            for ty in &q.types {
+                let count = match (ty, page) {
+                    (CounterType::Times, page) => {
+                        page.map(|p| p.times).unwrap_or_default()
+                            + buffered.get(canonical).cloned().unwrap_or_default()
+                    }
+                    (CounterType::Unique, _) => unique.get(canonical).cloned().unwrap_or_default(),
+                    (_, None) => 0,
+                    (CounterType::Reaction(_), Some(page)) => {
+                        let key = self.reaction_key(ty);
+                        reactions
+                            .iter()
+                            .find(|(page_id, k, _)| *page_id == page.id && Some(k.as_str()) == key)
+                            .map(|(_, _, count)| *count)
+                            .unwrap_or_default()
+                    }
+                };
+                counter.insert(*ty, count);
+            }
+            result.push(counter);
+        }
+        Ok(result)
+    }
+
+    /// 增加浏览量或者切换访客自己的表态，返回最新的计数
+    pub async fn set_count(
+        &self,
+        visitor: &Visitor,
+        visit: &PageVisit,
+        req: &SetViewCount,
+    ) -> Result<i32> {
+        let path = normalize(&req.path, &self.path);
+        if let Some(title) = &req.title {
+            PageViewCounter::find_or_create_page(&self.db, &path, Some(title))
+                .await
+                .context("update page title failed")?;
+        }
+        let key = match req.r#type {
+            CounterType::Times => {
+                if let SetCountAction::Desc = req.action {
+                    Err(KnownWebError::bad_request("view count can't be decreased"))?;
+                }
+                return self.increase_times(&path, visitor, visit).await;
+            }
+            CounterType::Unique => {
+                Err(KnownWebError::bad_request("unique visitors are read only"))?
+            }
+            CounterType::Reaction(_) => self
+                .reaction_key(&req.r#type)
+                .ok_or_else(|| KnownWebError::bad_request("unsupported reaction"))?,
+        };
+
+        let txn = self.db.begin().await.context("begin transaction failed")?;
+        let page_id = PageViewCounter::find_or_create_id_by_path(&txn, &path)
+            .await
+            .context("find page failed")?;
+
+        let voter = page_reaction_voters::ActiveModel {
+            page_id: Set(page_id),
+            reaction_key: Set(key.to_string()),
+            voter: Set(visitor.key()),
+            ..Default::default()
+        };
+        let delta = match req.action {
+            SetCountAction::Asc => {
+                let inserted = PageReactionVoters::insert(voter)
+                    .on_conflict(
+                        OnConflict::columns([
+                            page_reaction_voters::Column::PageId,
+                            page_reaction_voters::Column::ReactionKey,
+                            page_reaction_voters::Column::Voter,
+                        ])
+                        .do_nothing()
+                        .to_owned(),
+                    )
+                    .exec_without_returning(&txn)
+                    .await
+                    .context("insert page reaction voter failed")?;
+                inserted as i32
+            }
+            SetCountAction::Desc => {
+                let deleted = PageReactionVoters::delete(voter)
+                    .exec(&txn)
+                    .await
+                    .context("delete page reaction voter failed")?;
+                -(deleted.rows_affected as i32)
+            }
+        };
+
+        if delta != 0 {
+            let reaction = page_reactions::ActiveModel {
+                page_id: Set(page_id),
+                reaction_key: Set(key.to_string()),
+                count: Set(delta.max(0)),
+            };
+            PageReactions::insert(reaction)
+                .on_conflict(
+                    OnConflict::columns([
+                        page_reactions::Column::PageId,
+                        page_reactions::Column::ReactionKey,
+                    ])
+                    .value(
+                        page_reactions::Column::Count,
+                        Expr::col((PageReactions, page_reactions::Column::Count)).add(delta),
+                    )
+                    .to_owned(),
+                )
+                .exec_without_returning(&txn)
+                .await
+                .context("update page reaction failed")?;
+        }
+
+        let count = PageReactions::find_by_id((page_id, key.to_string()))
+            .one(&txn)
+            .await
+            .context("find page reaction failed")?
+            .map(|r| r.count)
+            .unwrap_or_default();
+
+        txn.commit().await.context("commit transaction failed")?;
+        Ok(count)
+    }
+
+    async fn increase_times(
+        &self,
+        path: &str,
+        visitor: &Visitor,
+        visit: &PageVisit,
+    ) -> Result<i32> {
+        if !self.pageview.buffer {
+            if !self.count_visit(path, visitor, &visit.user_agent).await? {
+                let times = PageViewCounter::find_by_path(&self.db, path)
+                    .await
+                    .context("query view counter failed")?
+                    .map(|m| m.times)
+                    .unwrap_or_default();
+                return Ok(times);
+            }
+            self.record_stats(path, visit).await?;
+            let m = PageViewCounter::increase_by_path(&self.db, path, 1)
+                .await
+                .context("increase view count failed")?;
+            return Ok(m.times);
+        }
+        // 缓冲模式下只在缓存未命中时查询数据库，增量按页面当前路径记录
+        let (path, times) = self.cached_page(path).await?;
+        if self.count_visit(&path, visitor, &visit.user_agent).await? {
+            self.record_stats(&path, visit).await?;
+            let mut redis = self.redis.clone();
+            let _: i64 = redis
+                .hincr(PV_DELTA_KEY, &path, 1)
+                .await
+                .with_context(|| format!("hincrby {PV_DELTA_KEY} {path} failed"))?;
+        }
+        let buffered = self.buffered_times(&[path.clone()]).await?;
+        Ok(times + buffered.get(&path).cloned().unwrap_or_default())
+    }
+
+    /// 从缓存中读取页面当前路径和已写入数据库的浏览量，未命中时查询数据库
+    async fn cached_page(&self, path: &str) -> Result<(String, i32)> {
+        let mut redis = self.redis.clone();
+        let canonical: Option<String> = redis
+            .get(canonical_key(path))
+            .await
+            .with_context(|| format!("get {} failed", canonical_key(path)))?;
+        if let Some(canonical) = canonical {
+            let times: Option<i32> = redis
+                .get(base_times_key(&canonical))
+                .await
+                .with_context(|| format!("get {} failed", base_times_key(&canonical)))?;
+            if let Some(times) = times {
+                return Ok((canonical, times));
+            }
+        }
+        let page = PageViewCounter::find_by_path(&self.db, path)
+            .await
+            .context("query view counter failed")?;
+        let (canonical, times) = match page {
+            Some(page) => (page.path, page.times),
+            None => (path.to_string(), 0),
+        };
+        let _: () = redis::pipe()
+            .set_ex(canonical_key(path), &canonical, PAGE_CACHE_SECONDS)
+            .ignore()
+            .set_ex(base_times_key(&canonical), times, PAGE_CACHE_SECONDS)
+            .ignore()
+            .query_async(&mut redis)
+            .await
+            .with_context(|| format!("cache page {path} failed"))?;
+        Ok((canonical, times))
+    }
+
+    /// 记录独立访客，返回这次访问是否计入浏览量
+    async fn count_visit(&self, path: &str, visitor: &Visitor, user_agent: &str) -> Result<bool> {
+        if self.pageview.filter_crawlers && self.uaparser.is_crawler(user_agent) {
+            return Ok(false);
+        }
+        let mut redis = self.redis.clone();
+        let _: bool = redis
+            .pfadd(uv_key(path), visitor.key())
+            .await
+            .with_context(|| format!("pfadd {} failed", uv_key(path)))?;
+        if !self.pageview.dedup {
+            return Ok(true);
+        }
+        let opts = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(self.pageview.dedup_ttl));
+        let first_visit: Option<String> = redis
+            .set_options(seen_key(path, visitor), 1, opts)
+            .await
+            .with_context(|| format!("set {} failed", seen_key(path, visitor)))?;
+        Ok(first_visit.is_some())
+    }
+
+    /// 按天记录浏览量的来源、地区、浏览器和操作系统
+    async fn record_stats(&self, path: &str, visit: &PageVisit) -> Result<()> {
+        let day = Local::now().date_naive();
+        let stats = self.visit_dimensions(visit);
+        if !self.pageview.buffer {
+            let page_id = PageViewCounter::find_or_create_id_by_path(&self.db, path)
+                .await
+                .context("find page failed")?;
+            for (dimension, value) in &stats {
+                PageViewStats::increase(&self.db, page_id, day, *dimension, value, 1)
+                    .await
+                    .context("increase view stats failed")?;
+            }
+            return Ok(());
+        }
+        let mut pipe = redis::pipe();
+        for (dimension, value) in &stats {
+            let field = format!("{day}\t{}\t{value}\t{path}", dimension.as_ref());
+            pipe.hincr(PV_STATS_KEY, field, 1).ignore();
+        }
+        let mut redis = self.redis.clone();
+        let _: () = pipe
+            .query_async(&mut redis)
+            .await
+            .with_context(|| format!("hincrby {PV_STATS_KEY} failed"))?;
+        Ok(())
+    }
+
+    fn visit_dimensions(&self, visit: &PageVisit) -> Vec<(StatDimension, String)> {
+        let mut stats = vec![(StatDimension::Total, String::new())];
+        if let Some(referrer) = &visit.referrer {
+            stats.push((StatDimension::Referrer, referrer.clone()));
+        }
+        match ip2region::search_ip(&visit.ip.to_string()) {
+            Ok(location) => {
+                if let Some(contry) = location.contry {
+                    stats.push((StatDimension::Country, contry));
+                }
+            }
+            Err(e) => tracing::warn!("ip2region failed:{}", e),
+        }
+        let client = self.uaparser.parse(&visit.user_agent);
+        stats.push((StatDimension::Browser, client.user_agent.family.to_string()));
+        stats.push((StatDimension::Os, client.os.family.to_string()));
+        stats
+            .into_iter()
+            .map(|(d, v)| (d, v.chars().take(255).collect()))
+            .collect()
+    }
+
+    async fn unique_visitors(&self, paths: &[String]) -> Result<HashMap<String, i32>> {
+        let mut redis = self.redis.clone();
+        let mut unique = HashMap::with_capacity(paths.len());
+        for path in paths {
+            let count: i32 = redis
+                .pfcount(uv_key(path))
+                .await
+                .with_context(|| format!("pfcount {} failed", uv_key(path)))?;
+            unique.insert(path.clone(), count);
+        }
+        Ok(unique)
+    }
+
+    /// redis中还未写入数据库的浏览量，包括正在写入的部分
+    async fn buffered_times(&self, paths: &[String]) -> Result<HashMap<String, i32>> {
+        let mut buffered = HashMap::<String, i32>::new();
+        if !self.pageview.buffer || paths.is_empty() {
+            return Ok(buffered);
+        }
+        let mut redis = self.redis.clone();
+        for key in [PV_DELTA_KEY, PV_FLUSHING_KEY] {
+            let deltas: Vec<Option<i32>> = redis::cmd("HMGET")
+                .arg(key)
+                .arg(paths)
+                .query_async(&mut redis)
+                .await
+                .with_context(|| format!("hmget {key} failed"))?;
+            for (path, delta) in paths.iter().zip(deltas) {
+                *buffered.entry(path.clone()).or_default() += delta.unwrap_or_default();
+            }
+        }
+        Ok(buffered)
+    }
+
+    /// 将redis中累积的浏览量批量写入数据库，其他实例正在写入时跳过
+    pub async fn flush_buffer(&self) -> Result<usize> {
+        let Some(token) = self.lock_flushing(PV_FLUSHING_KEY).await? else {
+            return Ok(0);
+        };
+        let result = self.flush_buffer_locked().await;
+        self.unlock_flushing(PV_FLUSHING_KEY, &token).await?;
+        result
+    }
+
+    async fn flush_buffer_locked(&self) -> Result<usize> {
+        let mut redis = self.redis.clone();
+        let deltas = self.pending_deltas(PV_DELTA_KEY, PV_FLUSHING_KEY).await?;
+        for (path, delta) in &deltas {
+            let m = PageViewCounter::increase_by_path(&self.db, path, *delta)
+                .await
+                .with_context(|| format!("flush view count of {path} failed"))?;
+            // 逐个删除已写入的path，避免中途失败后重复累加；同时更新缓存的浏览量
+            let _: () = redis::pipe()
+                .atomic()
+                .set_ex(base_times_key(&m.path), m.times, PAGE_CACHE_SECONDS)
+                .ignore()
+                .hdel(PV_FLUSHING_KEY, path)
+                .ignore()
+                .query_async(&mut redis)
+                .await
+                .with_context(|| format!("hdel {PV_FLUSHING_KEY} {path} failed"))?;
+        }
+        Ok(deltas.len())
+    }
+
+    /// 将redis中累积的按天统计批量写入数据库，其他实例正在写入时跳过
+    pub async fn flush_stats(&self) -> Result<usize> {
+        let Some(token) = self.lock_flushing(PV_STATS_FLUSHING_KEY).await? else {
+            return Ok(0);
+        };
+        let result = self.flush_stats_locked().await;
+        self.unlock_flushing(PV_STATS_FLUSHING_KEY, &token).await?;
+        result
+    }
+
+    async fn flush_stats_locked(&self) -> Result<usize> {
+        let mut redis = self.redis.clone();
+        let deltas = self
+            .pending_deltas(PV_STATS_KEY, PV_STATS_FLUSHING_KEY)
+            .await?;
+        let mut page_ids = HashMap::<String, i32>::new();
+        for (field, delta) in &deltas {
+            if let Some((day, dimension, value, path)) = parse_stats_field(field) {
+                let page_id = match page_ids.get(path) {
+                    Some(id) => *id,
+                    None => {
+                        let id = PageViewCounter::find_or_create_id_by_path(&self.db, path)
+                            .await
+                            .context("find page failed")?;
+                        *page_ids.entry(path.to_string()).or_insert(id)
+                    }
+                };
+                PageViewStats::increase(&self.db, page_id, day, dimension, value, *delta)
+                    .await
+                    .with_context(|| format!("flush view stats of {path} failed"))?;
+            } else {
+                tracing::warn!("invalid view stats field: {}", field);
+            }
+            let _: () = redis
+                .hdel(PV_STATS_FLUSHING_KEY, field)
+                .await
+                .with_context(|| format!("hdel {PV_STATS_FLUSHING_KEY} failed"))?;
+        }
+        Ok(deltas.len())
+    }
+
+    /// 加锁成功时返回锁的token，释放锁时需要校验
+    async fn lock_flushing(&self, flushing_key: &str) -> Result<Option<String>> {
+        let lock_key = format!("{flushing_key}:lock");
+        let token = rand::rand_alphanumeric(16);
+        let opts = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(FLUSH_LOCK_SECONDS));
+        let mut redis = self.redis.clone();
+        let locked: Option<String> = redis
+            .set_options(&lock_key, &token, opts)
+            .await
+            .with_context(|| format!("set {lock_key} failed"))?;
+        Ok(locked.map(|_| token))
+    }
+
+    async fn unlock_flushing(&self, flushing_key: &str, token: &str) -> Result<()> {
+        let lock_key = format!("{flushing_key}:lock");
+        let mut redis = self.redis.clone();
+        let _: i64 = redis::Script::new(UNLOCK_SCRIPT)
+            .key(&lock_key)
+            .arg(token)
+            .invoke_async(&mut redis)
+            .await
+            .with_context(|| format!("unlock {lock_key} failed"))?;
+        Ok(())
+    }
+
+    /// 取出待写入的增量：先把delta_key改名为flushing_key，上次没写完的会继续写入
+    async fn pending_deltas(
+        &self,
+        delta_key: &str,
+        flushing_key: &str,
+    ) -> Result<HashMap<String, i32>> {
+        let mut redis = self.redis.clone();
+        let pending: bool = redis
+            .exists(flushing_key)
+            .await
+            .with_context(|| format!("check {flushing_key} failed"))?;
+        if !pending {
+            let exists: bool = redis
+                .exists(delta_key)
+                .await
+                .with_context(|| format!("check {delta_key} failed"))?;
+            if !exists {
+                return Ok(HashMap::new());
+            }
+            let _: () = redis
+                .rename(delta_key, flushing_key)
+                .await
+                .with_context(|| format!("rename {delta_key} failed"))?;
+        }
+        let deltas = redis
+            .hgetall(flushing_key)
+            .await
+            .with_context(|| format!("hgetall {flushing_key} failed"))?;
+        Ok(deltas)
+    }
+
+    /// 每天的总浏览量
+    pub async fn get_trend(&self, q: &StatsQuery) -> Result<Vec<TrendItem>> {
+        let mut select = PageViewStats::find()
+            .select_only()
+            .column(page_view_stats::Column::Day)
+            .column_as(page_view_stats::Column::Times.sum(), "times")
+            .filter(page_view_stats::Column::Day.between(q.start, q.end))
+            .filter(page_view_stats::Column::Dimension.eq(StatDimension::Total.as_ref()))
+            .group_by(page_view_stats::Column::Day)
+            .order_by_asc(page_view_stats::Column::Day);
+        if let Some(path) = &q.path {
+            let page = PageViewCounter::find_id_by_path(&self.db, normalize(path, &self.path))
+                .await
+                .context("find page failed")?;
+            let Some(page) = page else {
+                return Ok(vec![]);
+            };
+            select = select.filter(page_view_stats::Column::PageId.eq(page.id));
+        }
+        let trend: Vec<(Date, i64)> = select
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("query view trend failed")?;
+        Ok(trend
+            .into_iter()
+            .map(|(day, times)| TrendItem { day, times })
+            .collect())
+    }
+
+    /// 浏览量最高的页面
+    pub async fn get_top_pages(&self, q: &StatsQuery) -> Result<Vec<TopItem>> {
+        let top: Vec<(i32, i64)> = PageViewStats::find()
+            .select_only()
+            .column(page_view_stats::Column::PageId)
+            .column_as(page_view_stats::Column::Times.sum(), "times")
+            .filter(page_view_stats::Column::Day.between(q.start, q.end))
+            .filter(page_view_stats::Column::Dimension.eq(StatDimension::Total.as_ref()))
+            .group_by(page_view_stats::Column::PageId)
+            .order_by(page_view_stats::Column::Times.sum(), Order::Desc)
+            .limit(q.limit)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("query top pages failed")?;
+        let paths: HashMap<i32, String> = PageViewCounter::find()
+            .filter(page_view_counter::Column::Id.is_in(top.iter().map(|(id, _)| *id)))
+            .all(&self.db)
+            .await
+            .context("query pages failed")?
+            .into_iter()
+            .map(|p| (p.id, p.path))
+            .collect();
+        Ok(top
+            .into_iter()
+            .filter_map(|(id, times)| {
+                paths.get(&id).map(|path| TopItem {
+                    name: path.clone(),
+                    times,
+                })
+            })
+            .collect())
+    }
+
+    /// 某个维度下浏览量最高的取值，如来源域名、国家、浏览器、操作系统
+    pub async fn get_top(&self, dimension: StatDimension, q: &StatsQuery) -> Result<Vec<TopItem>> {
+        let mut select = PageViewStats::find()
+            .select_only()
+            .column(page_view_stats::Column::Value)
+            .column_as(page_view_stats::Column::Times.sum(), "times")
+            .filter(page_view_stats::Column::Day.between(q.start, q.end))
+            .filter(page_view_stats::Column::Dimension.eq(dimension.as_ref()))
+            .group_by(page_view_stats::Column::Value)
+            .order_by(page_view_stats::Column::Times.sum(), Order::Desc)
+            .limit(q.limit);
+        if let Some(path) = &q.path {
+            let page = PageViewCounter::find_id_by_path(&self.db, normalize(path, &self.path))
+                .await
+                .context("find page failed")?;
+            let Some(page) = page else {
+                return Ok(vec![]);
+            };
+            select = select.filter(page_view_stats::Column::PageId.eq(page.id));
+        }
+        let top: Vec<(String, i64)> = select
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .with_context(|| format!("query top {} failed", dimension.as_ref()))?;
+        Ok(top
+            .into_iter()
+            .map(|(name, times)| TopItem { name, times })
+            .collect())
+    }
+
+    fn reaction_key(&self, ty: &CounterType) -> Option<&str> {
+        match ty {
+            CounterType::Times | CounterType::Unique => None,
+            CounterType::Reaction(i) => self.reaction.page.get(*i).map(|k| k.as_str()),
+        }
+    }
+}
+
+fn parse_stats_field(field: &str) -> Option<(Date, StatDimension, &str, &str)> {
+    let mut parts = field.splitn(4, '\t');
+    let day = parts.next()?.parse().ok()?;
+    let dimension = parts.next()?.parse().ok()?;
+    let value = parts.next()?;
+    let path = parts.next()?;
+    Some((day, dimension, value, path))
+}
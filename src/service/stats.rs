@@ -0,0 +1,193 @@
+use crate::model::page_view_stats::{self, StatDimension};
+use crate::model::sea_orm_active_enums::CommentStatus;
+use crate::model::{comments, page_view_counter, prelude::*, users};
+use crate::views::stats::{
+    CommenterCount, DailyCount, PageCount, PageviewTotal, StatsQuery, StatsResp,
+};
+use anyhow::Context;
+use sea_orm::prelude::{Date, DateTime};
+use sea_orm::sea_query::{Alias, Expr, Func, SimpleExpr};
+use sea_orm::sqlx::types::chrono::{Days, Local};
+use sea_orm::{
+    ColumnTrait, EntityTrait, Order, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use spring::plugin::service::Service;
+use spring_redis::redis::AsyncCommands;
+use spring_redis::Redis;
+use spring_sea_orm::DbConn;
+use spring_web::error::Result;
+use std::collections::HashMap;
+
+/// 统计结果的缓存时间，单位秒
+const STATS_CACHE_TTL: u64 = 60;
+
+#[derive(Clone, Service)]
+pub struct StatsService {
+    #[component]
+    db: DbConn,
+    #[component]
+    redis: Redis,
+}
+
+impl StatsService {
+    /// 管理后台的概览数据，短时间缓存在redis中
+    pub async fn get_stats(&self, q: &StatsQuery) -> Result<StatsResp> {
+        let cache_key = format!("stats:dashboard:{}", q.days);
+        let mut redis = self.redis.clone();
+        let cached: Option<String> = redis
+            .get(&cache_key)
+            .await
+            .with_context(|| format!("get {cache_key} failed"))?;
+        if let Some(stats) = cached.and_then(|s| serde_json::from_str(&s).ok()) {
+            return Ok(stats);
+        }
+
+        let stats = self.compute_stats(q).await?;
+        let json = serde_json::to_string(&stats).context("serialize stats failed")?;
+        let _: () = redis
+            .set_ex(&cache_key, json, STATS_CACHE_TTL)
+            .await
+            .with_context(|| format!("set {cache_key} failed"))?;
+        Ok(stats)
+    }
+
+    async fn compute_stats(&self, q: &StatsQuery) -> Result<StatsResp> {
+        let today = Local::now().date_naive();
+        let start = today
+            .checked_sub_days(Days::new(q.days.saturating_sub(1) as u64))
+            .unwrap_or(today);
+        let since: DateTime = start.and_hms_opt(0, 0, 0).unwrap_or_default();
+
+        let comments_per_day: Vec<(Date, i64)> = Comments::find()
+            .select_only()
+            .column_as(date_of(comments::Column::CreatedAt), "day")
+            .column_as(comments::Column::Id.count(), "count")
+            .filter(comments::Column::CreatedAt.gte(since))
+            .filter(comments::Column::DeletedAt.is_null())
+            .group_by(date_of(comments::Column::CreatedAt))
+            .order_by_asc(date_of(comments::Column::CreatedAt))
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count comments per day failed")?;
+
+        let status = Comments::count_by_status(&self.db)
+            .await
+            .context("count comments by status failed")?;
+        let total = (status.approved + status.waiting + status.spam) as f64;
+        let ratio = |n: u64| if total > 0.0 { n as f64 / total } else { 0.0 };
+
+        let top_pages: Vec<(i32, i64)> = Comments::find()
+            .select_only()
+            .column(comments::Column::PageId)
+            .column_as(comments::Column::Id.count(), "count")
+            .filter(comments::Column::Status.eq(CommentStatus::Approved))
+            .filter(comments::Column::DeletedAt.is_null())
+            .filter(comments::Column::CreatedAt.gte(since))
+            .group_by(comments::Column::PageId)
+            .order_by(comments::Column::Id.count(), Order::Desc)
+            .limit(10)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count comments per page failed")?;
+        let paths: HashMap<i32, String> = PageViewCounter::find()
+            .filter(page_view_counter::Column::Id.is_in(top_pages.iter().map(|(id, _)| *id)))
+            .all(&self.db)
+            .await
+            .context("query pages failed")?
+            .into_iter()
+            .map(|p| (p.id, p.path))
+            .collect();
+
+        let top_commenters: Vec<(Option<String>, Option<String>, i64)> = Comments::find()
+            .select_only()
+            .column(comments::Column::Nick)
+            .column(comments::Column::Mail)
+            .column_as(comments::Column::Id.count(), "count")
+            .filter(comments::Column::Status.eq(CommentStatus::Approved))
+            .filter(comments::Column::DeletedAt.is_null())
+            .filter(comments::Column::CreatedAt.gte(since))
+            .group_by(comments::Column::Nick)
+            .group_by(comments::Column::Mail)
+            .order_by(comments::Column::Id.count(), Order::Desc)
+            .limit(10)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count comments per commenter failed")?;
+
+        let pageview_total: Option<i64> = PageViewCounter::find()
+            .select_only()
+            .column_as(page_view_counter::Column::Times.sum(), "times")
+            .into_tuple()
+            .one(&self.db)
+            .await
+            .context("sum view count failed")?
+            .flatten();
+        let pageview_period: Option<i64> = PageViewStats::find()
+            .select_only()
+            .column_as(page_view_stats::Column::Times.sum(), "times")
+            .filter(page_view_stats::Column::Dimension.eq(StatDimension::Total.as_ref()))
+            .filter(page_view_stats::Column::Day.gte(start))
+            .into_tuple()
+            .one(&self.db)
+            .await
+            .context("sum view stats failed")?
+            .flatten();
+
+        let user_growth: Vec<(Date, i64)> = Users::find()
+            .select_only()
+            .column_as(date_of(users::Column::CreatedAt), "day")
+            .column_as(users::Column::Id.count(), "count")
+            .filter(users::Column::CreatedAt.gte(since))
+            .group_by(date_of(users::Column::CreatedAt))
+            .order_by_asc(date_of(users::Column::CreatedAt))
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context("count users per day failed")?;
+        let user_count = Users::find()
+            .count(&self.db)
+            .await
+            .context("count users failed")?;
+
+        Ok(StatsResp {
+            comments_per_day: daily(comments_per_day),
+            approval_ratio: ratio(status.approved),
+            spam_ratio: ratio(status.spam),
+            status,
+            top_pages: top_pages
+                .into_iter()
+                .filter_map(|(id, count)| {
+                    paths.get(&id).map(|path| PageCount {
+                        path: path.clone(),
+                        count,
+                    })
+                })
+                .collect(),
+            top_commenters: top_commenters
+                .into_iter()
+                .map(|(nick, mail, count)| CommenterCount { nick, mail, count })
+                .collect(),
+            pageview: PageviewTotal {
+                total: pageview_total.unwrap_or_default(),
+                period: pageview_period.unwrap_or_default(),
+            },
+            user_growth: daily(user_growth),
+            user_count,
+        })
+    }
+}
+
+/// `date(column)`，按天分组
+fn date_of<C: ColumnTrait>(col: C) -> SimpleExpr {
+    Func::cust(Alias::new("date")).arg(Expr::col(col)).into()
+}
+
+fn daily(counts: Vec<(Date, i64)>) -> Vec<DailyCount> {
+    counts
+        .into_iter()
+        .map(|(day, count)| DailyCount { day, count })
+        .collect()
+}
@@ -0,0 +1,13 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 游标对客户端是不透明的：hex(json)
+pub fn encode<T: Serialize>(cursor: &T) -> String {
+    let json = serde_json::to_vec(cursor).expect("cursor serialize failed");
+    base16ct::lower::encode_string(&json)
+}
+
+pub fn decode<T: DeserializeOwned>(cursor: &str) -> Option<T> {
+    let json = base16ct::mixed::decode_vec(cursor).ok()?;
+    serde_json::from_slice(&json).ok()
+}
@@ -0,0 +1,154 @@
+use crate::config::comrak::ComrakConfig;
+use crate::config::sanitizer::{SanitizerConfig, SanitizerPolicy};
+use crate::model::comments::Mention;
+use comrak::nodes::{AstNode, NodeHtmlBlock, NodeValue};
+use comrak::plugins::syntect::SyntectAdapter;
+use comrak::{format_html_with_plugins, parse_document, Arena, Options, Plugins};
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use md5::{Digest, Md5};
+use std::sync::OnceLock;
+
+/// 渲染逻辑变化时递增，让已缓存的html全部失效
+const RENDERER_VERSION: u32 = 2;
+
+/// syntect内置的主题，配置其他主题时退回css class
+const SYNTECT_THEMES: [&str; 7] = [
+    "base16-ocean.dark",
+    "base16-eighties.dark",
+    "base16-mocha.dark",
+    "base16-ocean.light",
+    "InspiredGitHub",
+    "Solarized (dark)",
+    "Solarized (light)",
+];
+
+/// latex2mathml生成的标签和属性
+const MATHML_TAGS: &[&str] = &[
+    "math",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "mpadded",
+    "mrow",
+    "mstyle",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "msub",
+    "msup",
+    "msubsup",
+    "munder",
+    "mover",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+];
+const MATHML_ATTRIBUTES: &[&str] = &[
+    "display",
+    "mathvariant",
+    "stretchy",
+    "fence",
+    "separator",
+    "lspace",
+    "rspace",
+    "width",
+    "displaystyle",
+    "columnalign",
+];
+
+/// 加载语法和主题比较慢，整个进程共用一个
+static SYNTECT: OnceLock<SyntectAdapter> = OnceLock::new();
+
+/// 渲染结果的版本号，渲染逻辑或者[comrak]、[sanitizer]配置变化时随之变化，
+/// 使用md5保证不同版本的rust编译出来的结果一致
+pub fn render_version(comrak: &ComrakConfig, sanitizer: &SanitizerConfig) -> i64 {
+    let config = serde_json::to_vec(&(RENDERER_VERSION, comrak, sanitizer))
+        .expect("serialize render config failed");
+    let digest = Md5::digest(config);
+    let mut version = [0u8; 8];
+    version.copy_from_slice(&digest[..8]);
+    i64::from_be_bytes(version)
+}
+
+/// 把评论内容渲染成html，回复的评论会在开头加上`[@nick](#pid): `
+pub fn render(
+    content: &str,
+    mention: Option<&Mention>,
+    comrak: &ComrakConfig,
+    policy: &SanitizerPolicy,
+) -> String {
+    let content = match mention {
+        Some(mention) => format!("{}{}", mention.to_markdown(), content),
+        None => content.to_string(),
+    };
+    let options: Options = comrak.into();
+    let arena = Arena::new();
+    let root = parse_document(&arena, &content, &options);
+    if comrak.math_render {
+        render_math(root);
+    }
+    let mut plugins = Plugins::default();
+    if comrak.highlight {
+        let adapter = syntect_adapter(comrak.highlight_theme.as_deref());
+        plugins.render.codefence_syntax_highlighter = Some(adapter);
+    }
+    let mut html = vec![];
+    format_html_with_plugins(root, &options, &mut html, &plugins)
+        .expect("write html to memory failed");
+    let html = String::from_utf8_lossy(&html);
+
+    let mut builder = policy.builder();
+    if comrak.math_render {
+        builder.add_tags(MATHML_TAGS);
+        for tag in MATHML_TAGS {
+            builder.add_tag_attributes(*tag, MATHML_ATTRIBUTES);
+        }
+    }
+    builder.clean(&html).to_string()
+}
+
+/// 把公式节点替换成MathML，公式有语法错误时保留原样
+fn render_math<'a>(root: &'a AstNode<'a>) {
+    for node in root.descendants() {
+        let mut data = node.data.borrow_mut();
+        let mathml = match &data.value {
+            NodeValue::Math(math) => {
+                let style = match math.display_math {
+                    true => DisplayStyle::Block,
+                    false => DisplayStyle::Inline,
+                };
+                latex_to_mathml(&math.literal, style).map(NodeValue::HtmlInline)
+            }
+            NodeValue::CodeBlock(code) if code.info == "math" => {
+                latex_to_mathml(&code.literal, DisplayStyle::Block).map(|literal| {
+                    NodeValue::HtmlBlock(NodeHtmlBlock {
+                        block_type: 0,
+                        literal: literal + "\n",
+                    })
+                })
+            }
+            _ => continue,
+        };
+        match mathml {
+            Ok(value) => data.value = value,
+            Err(e) => tracing::debug!("render math failed: {}", e),
+        }
+    }
+}
+
+fn syntect_adapter(theme: Option<&str>) -> &'static SyntectAdapter {
+    SYNTECT.get_or_init(|| {
+        let theme = theme.filter(|theme| {
+            let known = SYNTECT_THEMES.contains(theme);
+            if !known {
+                tracing::warn!("unknown highlight theme {}, fallback to css class", theme);
+            }
+            known
+        });
+        SyntectAdapter::new(theme)
+    })
+}
@@ -1,6 +1,10 @@
 pub mod avatar;
+pub mod cursor;
 pub mod ip2region;
 pub mod jwt;
 pub mod mail;
+pub mod markdown;
+pub mod path;
 pub mod rand;
 pub mod validate_code;
+pub mod visitor;
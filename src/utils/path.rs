@@ -0,0 +1,180 @@
+use crate::config::path::PathConfig;
+use itertools::Itertools;
+
+/// 按照配置规范化页面路径，如`/post/a/?utm_source=x#comments` => `/post/a`
+pub fn normalize(path: &str, config: &PathConfig) -> String {
+    let mut path = path.trim();
+    // 兼容传入完整url的情况，只保留路径部分
+    for scheme in ["http://", "https://"] {
+        if let Some(rest) = path.strip_prefix(scheme) {
+            path = rest.find('/').map(|i| &rest[i..]).unwrap_or("/");
+        }
+    }
+
+    let (path, fragment) = match path.split_once('#') {
+        Some((p, f)) => (p, Some(f)),
+        None => (path, None),
+    };
+    let (path, query) = match path.split_once('?') {
+        Some((p, q)) => (p, Some(q)),
+        None => (path, None),
+    };
+
+    let mut path = match config.percent_decode {
+        true => percent_decode(path),
+        false => path.to_string(),
+    };
+    if config.lowercase {
+        path = path.to_lowercase();
+    }
+    if config.strip_index {
+        for index in ["index.html", "index.htm"] {
+            if path.ends_with(&format!("/{index}")) {
+                path.truncate(path.len() - index.len());
+                break;
+            }
+        }
+    }
+    if config.trailing_slash {
+        let trimmed = path.trim_end_matches('/').len();
+        path.truncate(trimmed);
+    }
+    if !path.starts_with('/') {
+        path.insert(0, '/');
+    }
+
+    let query = query
+        .map(|q| {
+            q.split('&')
+                .filter(|pair| {
+                    let key = pair.split('=').next().unwrap_or_default();
+                    config.allowed_query.iter().any(|k| k == key)
+                })
+                .join("&")
+        })
+        .filter(|q| !q.is_empty());
+    if let Some(query) = query {
+        path.push('?');
+        path.push_str(&query);
+    }
+    if let Some(fragment) = fragment.filter(|_| !config.strip_fragment) {
+        path.push('#');
+        path.push_str(fragment);
+    }
+    path
+}
+
+/// 解码`%XX`，解码结果不是合法的utf8时保留原样；`%2F`会改变路径层级，不解码
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            let b = hex.and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = b.filter(|b| *b != b'/') {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PathConfig {
+        PathConfig {
+            trailing_slash: true,
+            strip_index: true,
+            allowed_query: vec![],
+            lowercase: false,
+            strip_fragment: true,
+            percent_decode: true,
+        }
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let c = config();
+        assert_eq!(normalize("/post/a/", &c), "/post/a");
+        assert_eq!(normalize("/post/a//", &c), "/post/a");
+        assert_eq!(normalize("/", &c), "/");
+        assert_eq!(normalize("", &c), "/");
+        let c = PathConfig {
+            trailing_slash: false,
+            ..config()
+        };
+        assert_eq!(normalize("/post/a/", &c), "/post/a/");
+    }
+
+    #[test]
+    fn strip_index() {
+        let c = config();
+        assert_eq!(normalize("/post/a/index.html", &c), "/post/a");
+        assert_eq!(normalize("/index.htm", &c), "/");
+        assert_eq!(normalize("/post/myindex.html", &c), "/post/myindex.html");
+        let c = PathConfig {
+            trailing_slash: false,
+            ..config()
+        };
+        assert_eq!(normalize("/post/a/index.html", &c), "/post/a/");
+    }
+
+    #[test]
+    fn query_and_fragment() {
+        let c = config();
+        assert_eq!(normalize("/post/a/?utm_source=x#comments", &c), "/post/a");
+        assert_eq!(normalize("/post/a#x?y=1", &c), "/post/a");
+        let c = PathConfig {
+            allowed_query: vec!["p".to_string()],
+            strip_fragment: false,
+            ..config()
+        };
+        assert_eq!(normalize("/?p=1&utm_source=x", &c), "/?p=1");
+        assert_eq!(normalize("/a?utm_source=x#top", &c), "/a#top");
+    }
+
+    #[test]
+    fn full_url() {
+        let c = config();
+        assert_eq!(normalize("https://example.com/post/a/", &c), "/post/a");
+        assert_eq!(normalize("http://example.com", &c), "/");
+        assert_eq!(normalize(" /post/a ", &c), "/post/a");
+        assert_eq!(normalize("post/a", &c), "/post/a");
+    }
+
+    #[test]
+    fn lowercase() {
+        let c = PathConfig {
+            lowercase: true,
+            ..config()
+        };
+        assert_eq!(normalize("/Post/A", &c), "/post/a");
+        assert_eq!(normalize("/Post/A", &config()), "/Post/A");
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("/%E4%BD%A0%E5%A5%BD"), "/你好");
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/a%41"), "/aA");
+        assert_eq!(percent_decode("/a%2Fb"), "/a%2Fb");
+        assert_eq!(percent_decode("/a%2fb"), "/a%2fb");
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/a%4"), "/a%4");
+        assert_eq!(percent_decode("/a%zzb"), "/a%zzb");
+        assert_eq!(percent_decode("/%FF%FE"), "/%FF%FE");
+        let c = PathConfig {
+            percent_decode: false,
+            ..config()
+        };
+        assert_eq!(normalize("/a%20b", &c), "/a%20b");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::utils::jwt::OptionalClaims;
+use axum_client_ip::SecureClientIp;
+use md5::{Digest, Md5};
+use spring_web::async_trait;
+use spring_web::axum::http::header;
+use spring_web::axum::http::request::Parts;
+use spring_web::axum::http::Uri;
+use spring_web::error::{KnownWebError, WebError};
+use spring_web::extractor::FromRequestParts;
+use std::net::IpAddr;
+
+/// 点赞、表态等操作的访客身份：登录用户用uid，匿名用户用IP+UA的哈希
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Visitor {
+    User(i32),
+    Anonymous(String),
+}
+
+impl Visitor {
+    pub fn key(&self) -> String {
+        match self {
+            Self::User(uid) => format!("user:{uid}"),
+            Self::Anonymous(fingerprint) => format!("anon:{fingerprint}"),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Self::User(uid) => Some(*uid),
+            Self::Anonymous(_) => None,
+        }
+    }
+}
+
+pub fn fingerprint(ip: &IpAddr, ua: &str) -> String {
+    let mut hasher = Md5::new();
+    hasher.update(ip.to_string());
+    hasher.update("|");
+    hasher.update(ua);
+    let hash = hasher.finalize();
+    base16ct::lower::encode_string(&hash)
+}
+
+pub fn user_agent(parts: &Parts) -> &str {
+    parts
+        .headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Visitor
+where
+    S: Send + Sync,
+{
+    type Rejection = WebError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let claims = OptionalClaims::from_request_parts(parts, state).await?;
+        if let Some(claims) = &*claims {
+            return Ok(Self::User(claims.uid));
+        }
+        let SecureClientIp(ip) = SecureClientIp::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, msg)| KnownWebError::internal_server_error(msg))?;
+        Ok(Self::Anonymous(fingerprint(&ip, user_agent(parts))))
+    }
+}
+
+/// 一次页面浏览的来源信息，用于按天统计浏览量
+#[derive(Debug, Clone)]
+pub struct PageVisit {
+    pub ip: IpAddr,
+    pub user_agent: String,
+    /// Referer中的域名
+    pub referrer: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PageVisit
+where
+    S: Send + Sync,
+{
+    type Rejection = WebError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let SecureClientIp(ip) = SecureClientIp::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, msg)| KnownWebError::internal_server_error(msg))?;
+        let referrer = parts
+            .headers
+            .get(header::REFERER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<Uri>().ok())
+            .and_then(|uri| uri.host().map(|h| h.to_lowercase()));
+        Ok(Self {
+            ip,
+            user_agent: user_agent(parts).to_string(),
+            referrer,
+        })
+    }
+}
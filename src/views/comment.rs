@@ -1,10 +1,12 @@
+use crate::model::comment_votes;
 use crate::model::comments;
+use crate::model::comments::ModerationVerdict;
 use crate::model::sea_orm_active_enums::CommentStatus;
 use crate::model::sea_orm_active_enums::UserType;
 use derive_more::derive::From;
 use sea_orm::prelude::DateTime;
-use sea_orm::Order;
-use sea_orm::Set;
+use sea_orm::sea_query::SimpleExpr;
+use sea_orm::{ColumnTrait, FromQueryResult, Order, Set, Value};
 use serde::{Deserialize, Serialize};
 use serde_with::formats::CommaSeparator;
 use serde_with::serde_as;
@@ -12,20 +14,33 @@ use serde_with::skip_serializing_none;
 use serde_with::BoolFromInt;
 use serde_with::DisplayFromStr;
 use serde_with::StringWithSeparator;
+use std::collections::HashMap;
 use validator::Validate;
 
 #[serde_as]
 #[derive(Debug, Validate, Deserialize)]
 pub struct AdminCommentQuery {
+    #[validate(range(min = 1, message = "页码不合法"))]
+    #[serde(default = "default_page")]
     #[serde_as(as = "DisplayFromStr")]
     pub page: u64,
-    #[validate(range(max = 200, message = "查询数据过多"))]
+    #[validate(range(min = 1, max = 200, message = "查询数据过多"))]
     #[serde(default = "default_size")]
     pub size: u64,
     pub status: CommentStatus,
     pub owner: Owner,
     #[validate(length(max = 32, message = "查询关键字过长"))]
     pub keyword: Option<String>,
+    /// 查询回收站中已删除的评论
+    #[serde(default)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub trash: bool,
+    /// 上一页返回的next游标，传入时忽略page
+    pub cursor: Option<String>,
+}
+
+fn default_page() -> u64 {
+    1
 }
 
 fn default_size() -> u64 {
@@ -50,9 +65,11 @@ pub struct ListCommentQuery {
     pub limit: u64,
     #[serde(flatten)]
     pub sort_by: OrderBy,
+    /// 上一页返回的next游标，传入时使用keyset pagination，忽略offset
+    pub cursor: Option<String>,
 }
 
-/// 写在这里面方便后期数据量过大时，将limit pagination改造为keyset pagination
+/// 排序方式，offset兼容Waline客户端的分页
 #[serde_as]
 #[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "sortBy")]
@@ -93,6 +110,100 @@ impl OrderBy {
     }
 }
 
+/// keyset pagination的游标，记录上一页最后一条评论的排序字段
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentCursor {
+    pub sticky: bool,
+    pub star: i32,
+    pub created_at: DateTime,
+    pub id: i32,
+}
+
+impl From<&comments::Model> for CommentCursor {
+    fn from(c: &comments::Model) -> Self {
+        Self {
+            sticky: c.sticky,
+            star: c.star,
+            created_at: c.created_at,
+            id: c.id,
+        }
+    }
+}
+
+impl CommentCursor {
+    /// `(column, id)`按order排序时位于游标之后的记录
+    pub fn after(&self, column: comments::Column, order: &Order) -> SimpleExpr {
+        let value: Value = match column {
+            comments::Column::Star => self.star.into(),
+            _ => self.created_at.into(),
+        };
+        let after = |col: comments::Column, v: Value| match order {
+            Order::Asc => col.gt(v),
+            _ => col.lt(v),
+        };
+        let tie = column
+            .eq(value.clone())
+            .and(after(comments::Column::Id, self.id.into()));
+        after(column, value).or(tie)
+    }
+
+    /// 先按sticky倒序，再按`(column, id)`排序时位于游标之后的记录
+    pub fn after_sticky(&self, column: comments::Column, order: &Order) -> SimpleExpr {
+        let same_sticky = comments::Column::Sticky
+            .eq(self.sticky)
+            .and(self.after(column, order));
+        match self.sticky {
+            true => comments::Column::Sticky.eq(false).or(same_sticky),
+            false => same_sticky,
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Validate, Deserialize)]
+pub struct SearchCommentQuery {
+    #[validate(length(min = 1, max = 64, message = "查询关键字长度不合法"))]
+    pub q: String,
+    /// 只搜索某个页面的评论
+    pub path: Option<String>,
+    #[validate(range(min = 1, max = 100, message = "查询数据过多"))]
+    #[serde(default = "default_size")]
+    #[serde_as(as = "DisplayFromStr")]
+    pub limit: u64,
+    #[serde(default)]
+    #[serde_as(as = "DisplayFromStr")]
+    pub offset: u64,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub struct SearchRow {
+    pub id: i32,
+    pub page_id: i32,
+    pub nick: Option<String>,
+    pub mail: Option<String>,
+    pub status: CommentStatus,
+    pub created_at: DateTime,
+    pub highlight: String,
+    pub rank: f32,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub object_id: i32,
+    pub url: Option<String>,
+    pub page_title: Option<String>,
+    pub nick: Option<String>,
+    /// 仅管理员可见
+    pub mail: Option<String>,
+    pub status: Option<CommentStatus>,
+    /// 命中关键字的片段，关键字用`<mark>`包裹
+    pub highlight: String,
+    pub rank: f32,
+    pub time: i64,
+}
+
 #[serde_as]
 #[derive(Debug, Validate, Deserialize)]
 pub struct RecentCommentQuery {
@@ -125,6 +236,97 @@ impl Validate for CommentQueryReq {
     }
 }
 
+#[derive(Debug, Validate, Deserialize)]
+pub struct BatchCommentReq {
+    #[validate(length(max = 500, message = "操作数据过多"))]
+    pub ids: Option<Vec<i32>>,
+    #[validate(nested)]
+    pub filter: Option<AdminCommentFilter>,
+    pub action: BatchAction,
+}
+
+/// 与AdminCommentQuery相同的筛选条件
+#[derive(Debug, Validate, Deserialize)]
+pub struct AdminCommentFilter {
+    pub status: CommentStatus,
+    pub owner: Owner,
+    #[validate(length(max = 32, message = "查询关键字过长"))]
+    pub keyword: Option<String>,
+    #[serde(default)]
+    pub trash: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchAction {
+    Approve,
+    Spam,
+    Waiting,
+    Sticky,
+    Unsticky,
+    /// 移入回收站
+    Delete,
+    /// 从回收站恢复
+    Restore,
+    /// 彻底删除评论及其回复
+    Purge,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoteReq {
+    pub vote: VoteAction,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteAction {
+    Up,
+    Down,
+    Cancel,
+}
+
+impl VoteAction {
+    pub fn value(self) -> i16 {
+        match self {
+            Self::Up => comment_votes::UP,
+            Self::Down => comment_votes::DOWN,
+            Self::Cancel => 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReactionReq {
+    pub reaction: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentReactionQuery {
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommentReactionsResp {
+    /// 每个表情的回应数
+    pub count: HashMap<String, i64>,
+    /// 当前访客回应过的表情
+    pub mine: Vec<String>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct PurgeSpamReq {
+    /// 清理多少天之前的垃圾评论
+    #[validate(range(max = 3650))]
+    pub days: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RebuildHtmlReq {
+    /// 为true时重新渲染所有评论，否则只渲染缓存过期的评论
+    #[serde(default)]
+    pub force: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub enum Owner {
     #[serde(rename = "all")]
@@ -148,6 +350,13 @@ pub struct ListResp {
     pub count: u64,
     pub total_pages: u64,
     pub data: Vec<CommentResp>,
+    /// 页面已关闭评论
+    pub locked: bool,
+    /// 页面的评论区已隐藏
+    pub hidden: bool,
+    /// 下一页的游标，没有更多数据时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -158,22 +367,63 @@ pub struct AdminListResp {
     pub spam_count: u64,
     pub waiting_count: u64,
     pub data: Vec<CommentResp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// message为locales中的key，长度受限于数据库字段
+#[derive(Debug, Clone, Validate, Serialize, Deserialize)]
 pub struct AddCommentReq {
     pub comment: String,
+    #[validate(length(max = 1024, message = "field_too_long"))]
     pub ua: String,
+    #[validate(length(max = 255, message = "field_too_long"))]
     pub url: String,
+    #[validate(length(max = 255, message = "field_too_long"))]
     pub at: Option<String>,
+    #[validate(length(max = 255, message = "field_too_long"))]
     pub nick: Option<String>,
+    #[validate(
+        url(message = "invalid_link"),
+        length(max = 255, message = "field_too_long")
+    )]
     pub link: Option<String>,
+    #[validate(
+        email(message = "invalid_mail"),
+        length(max = 255, message = "field_too_long")
+    )]
     pub mail: Option<String>,
     pub pid: Option<i32>,
+    /// Waline客户端会传rid，服务端忽略并根据pid推导
     pub rid: Option<i32>,
+    /// 页面标题
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewCommentReq {
+    pub comment: String,
+    /// 回复的评论，预览时同样渲染`[@nick](#pid): `
+    pub pid: Option<i32>,
+    pub at: Option<String>,
 }
 
 impl AddCommentReq {
+    /// 前端未填写的字段会传空字符串，统一视为None；网址没有协议时补上https://
+    pub fn normalize_fields(&mut self) {
+        for field in [&mut self.nick, &mut self.mail, &mut self.link, &mut self.at] {
+            *field = field
+                .take()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+        }
+        if let Some(link) = &mut self.link {
+            if !link.contains("://") {
+                *link = format!("https://{link}");
+            }
+        }
+    }
+
     pub fn into_active_model(self, page_id: i32) -> comments::ActiveModel {
         comments::ActiveModel {
             page_id: Set(page_id),
@@ -182,8 +432,9 @@ impl AddCommentReq {
             ua: Set(self.ua),
             link: Set(self.link),
             mail: Set(self.mail),
-            pid: Set(self.pid.unwrap_or(0)),
-            rid: Set(self.rid.unwrap_or(0)),
+            // pid和rid由服务端根据被回复的评论推导，这里先作为根评论
+            pid: Set(comments::root_comment_id()),
+            rid: Set(comments::root_comment_id()),
             ..Default::default()
         }
     }
@@ -207,14 +458,32 @@ pub struct CommentResp {
     pub avatar: String,
     pub sticky: bool,
     pub like: i32,
+    pub dislike: i32,
+    /// 当前访客的投票：1为赞，-1为踩
+    pub my_vote: Option<i16>,
+    pub reactions: CommentReactionsResp,
     pub object_id: i32,
+    /// 回复的层级，根评论为0
     pub level: i32,
     pub browser: String,
     pub os: String,
     pub orig: Option<String>,
     pub addr: Option<String>,
+    /// 仅管理员可见的审核结论
+    pub moderation: Option<ModerationVerdict>,
+    /// 已删除的评论仍有回复时以占位符展示
+    pub deleted: Option<bool>,
+    pub edited: Option<bool>,
+    pub edited_at: Option<i64>,
+    /// 评论所在的页面，仅在最新评论和管理列表中返回
+    pub url: Option<String>,
+    pub page_title: Option<String>,
     pub time: i64,
     pub children: Vec<CommentResp>,
+    /// 根评论的回复总数，children只包含前reply_limit条
+    pub children_count: Option<u64>,
+    /// 继续查询回复的游标，配合rid使用
+    pub children_next: Option<String>,
 }
 
 #[serde_as]
@@ -1,4 +1,6 @@
 pub mod comment;
 pub mod oauth;
+pub mod page;
 pub mod pv_counter;
+pub mod stats;
 pub mod user;
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct RenamePageReq {
+    pub from: String,
+    pub to: String,
+    /// 旧路径保留为别名
+    #[serde(default = "default_true")]
+    pub keep_alias: bool,
+}
+
+/// 把from页面合并到to页面，from保留为别名
+#[derive(Debug, Deserialize)]
+pub struct MergePageReq {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageAliasReq {
+    pub path: String,
+    pub target: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageAliasQuery {
+    pub path: String,
+}
+
+/// 修改页面信息，未传入的字段保持不变
+#[derive(Debug, Deserialize)]
+pub struct PageMetaReq {
+    pub path: String,
+    pub title: Option<String>,
+    pub locked: Option<bool>,
+    pub hidden: Option<bool>,
+}
+
+fn default_true() -> bool {
+    true
+}
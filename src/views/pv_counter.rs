@@ -1,11 +1,11 @@
-use crate::model::page_view_counter;
-use sea_orm::DeriveColumn;
-use sea_orm::EnumIter;
-use serde::Deserialize;
-use serde::Serialize;
+use sea_orm::prelude::Date;
+use serde::{Deserialize, Serialize};
 use serde_with::formats::CommaSeparator;
 use serde_with::serde_as;
 use serde_with::StringWithSeparator;
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+use std::fmt::Display;
+use std::str::FromStr;
 
 #[serde_as]
 #[derive(Debug, Deserialize)]
@@ -13,8 +13,8 @@ pub struct ViewCountQuery {
     #[serde_as(as = "StringWithSeparator::<CommaSeparator, String>")]
     pub path: Vec<String>,
     #[serde(rename = "type")]
-    #[serde_as(as = "StringWithSeparator::<CommaSeparator, ColumnQueryAs>")]
-    pub types: Vec<ColumnQueryAs>,
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, CounterType>")]
+    pub types: Vec<CounterType>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,7 +22,9 @@ pub struct SetViewCount {
     pub path: String,
     #[serde(default)]
     pub action: SetCountAction,
-    pub r#type: ColumnQueryAs,
+    pub r#type: CounterType,
+    /// 页面标题
+    pub title: Option<String>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -34,37 +36,63 @@ pub enum SetCountAction {
     Desc,
 }
 
-#[derive(
-    PartialEq, Eq, Hash, Copy, Clone, Debug, EnumIter, DeriveColumn, Serialize, Deserialize,
-)]
-#[serde(rename_all = "lowercase")]
-pub enum ColumnQueryAs {
-    #[serde(rename = "time")]
+/// 兼容Waline的`time`和`reaction0`..`reaction8`，
+/// reactionN对应`[reaction].page`中配置的第N个表态，`unique`为独立访客数
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, SerializeDisplay, DeserializeFromStr)]
+pub enum CounterType {
     Times,
-    Reaction0,
-    Reaction1,
-    Reaction2,
-    Reaction3,
-    Reaction4,
-    Reaction5,
-    Reaction6,
-    Reaction7,
-    Reaction8,
+    Unique,
+    Reaction(usize),
 }
 
-impl ColumnQueryAs {
-    pub fn get(&self, model: &page_view_counter::Model) -> i32 {
+impl FromStr for CounterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "time" => return Ok(Self::Times),
+            "unique" => return Ok(Self::Unique),
+            _ => {}
+        }
+        s.strip_prefix("reaction")
+            .and_then(|i| i.parse().ok())
+            .map(Self::Reaction)
+            .ok_or_else(|| format!("unknown counter type: {s}"))
+    }
+}
+
+impl Display for CounterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Times => model.times,
-            Self::Reaction0 => model.reaction0,
-            Self::Reaction1 => model.reaction1,
-            Self::Reaction2 => model.reaction2,
-            Self::Reaction3 => model.reaction3,
-            Self::Reaction4 => model.reaction4,
-            Self::Reaction5 => model.reaction5,
-            Self::Reaction6 => model.reaction6,
-            Self::Reaction7 => model.reaction7,
-            Self::Reaction8 => model.reaction8,
+            Self::Times => write!(f, "time"),
+            Self::Unique => write!(f, "unique"),
+            Self::Reaction(i) => write!(f, "reaction{i}"),
         }
     }
 }
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    pub start: Date,
+    pub end: Date,
+    /// 只统计某个页面
+    pub path: Option<String>,
+    #[serde(default = "default_stats_limit")]
+    pub limit: u64,
+}
+
+fn default_stats_limit() -> u64 {
+    10
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrendItem {
+    pub day: Date,
+    pub times: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopItem {
+    pub name: String,
+    pub times: i64,
+}
@@ -0,0 +1,56 @@
+use crate::model::comments::StatusCount;
+use sea_orm::prelude::Date;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// 统计最近多少天
+    #[serde(default = "default_days")]
+    pub days: u32,
+}
+
+fn default_days() -> u32 {
+    30
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsResp {
+    pub comments_per_day: Vec<DailyCount>,
+    pub status: StatusCount,
+    pub approval_ratio: f64,
+    pub spam_ratio: f64,
+    pub top_pages: Vec<PageCount>,
+    pub top_commenters: Vec<CommenterCount>,
+    pub pageview: PageviewTotal,
+    pub user_growth: Vec<DailyCount>,
+    pub user_count: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyCount {
+    pub day: Date,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageCount {
+    pub path: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommenterCount {
+    pub nick: Option<String>,
+    pub mail: Option<String>,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageviewTotal {
+    /// 所有页面的累计浏览量
+    pub total: i64,
+    /// 统计周期内的浏览量
+    pub period: i64,
+}